@@ -0,0 +1,17 @@
+ALTER TABLE owners
+  ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX owners_deleted_at_idx ON owners (deleted_at) WHERE deleted_at IS NOT NULL;
+
+CREATE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.updated_at = now();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER owners_set_updated_at
+  BEFORE UPDATE ON owners
+  FOR EACH ROW EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,13 @@
+ALTER TABLE owners ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+CREATE TABLE email_verification_tokens (
+  id SERIAL PRIMARY KEY,
+  owner_id INTEGER NOT NULL REFERENCES owners (id) ON DELETE CASCADE,
+  email VARCHAR(255) NOT NULL,
+  token_hash CHAR(64) NOT NULL UNIQUE,
+  expires_at TIMESTAMPTZ NOT NULL,
+  consumed_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX email_verification_tokens_owner_id_idx ON email_verification_tokens (owner_id);
@@ -0,0 +1,21 @@
+CREATE TABLE sessions (
+  id SERIAL PRIMARY KEY,
+  owner_id INTEGER NOT NULL REFERENCES owners (id) ON DELETE CASCADE,
+  token_hash CHAR(64) NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  expires_at TIMESTAMPTZ NOT NULL,
+  revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX sessions_owner_id_idx ON sessions (owner_id);
+
+CREATE TABLE password_reset_tokens (
+  id SERIAL PRIMARY KEY,
+  owner_id INTEGER NOT NULL REFERENCES owners (id) ON DELETE CASCADE,
+  token_hash CHAR(64) NOT NULL UNIQUE,
+  expires_at TIMESTAMPTZ NOT NULL,
+  consumed_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX password_reset_tokens_owner_id_idx ON password_reset_tokens (owner_id);
@@ -0,0 +1,17 @@
+CREATE TABLE roles (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR(64) NOT NULL UNIQUE
+);
+
+INSERT INTO roles (name) VALUES ('admin'), ('support'), ('owner');
+
+CREATE TABLE owner_roles (
+  owner_id INTEGER NOT NULL REFERENCES owners (id) ON DELETE CASCADE,
+  role_id INTEGER NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+  granted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  PRIMARY KEY (owner_id, role_id)
+);
+
+-- Every existing account is a regular owner. Staff roles are granted explicitly.
+INSERT INTO owner_roles (owner_id, role_id)
+SELECT owners.id, roles.id FROM owners CROSS JOIN roles WHERE roles.name = 'owner';
@@ -0,0 +1,13 @@
+ALTER TABLE owners ADD COLUMN erased_at TIMESTAMPTZ;
+
+-- Proof of who asked for an erasure. Deliberately no foreign keys: the record must outlive the
+-- purge of the erased owner and of the requester.
+CREATE TABLE owner_erasures (
+  id SERIAL PRIMARY KEY,
+  owner_id INTEGER NOT NULL,
+  requested_by INTEGER NOT NULL,
+  credential VARCHAR(64) NOT NULL,
+  erased_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX owner_erasures_owner_id_idx ON owner_erasures (owner_id);
@@ -0,0 +1,38 @@
+CREATE TABLE audit_events (
+  id BIGSERIAL PRIMARY KEY,
+  occurred_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  actor_owner_id INTEGER,
+  actor_credential VARCHAR(64),
+  action VARCHAR(64) NOT NULL,
+  target_type VARCHAR(64) NOT NULL,
+  target_id INTEGER NOT NULL,
+  request_id VARCHAR(128),
+  diff JSONB NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX audit_events_target_idx ON audit_events (target_type, target_id, occurred_at);
+
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at);
+
+-- Rows can't be changed or removed. The only exception is an owner erasure scrubbing the diff
+-- values of its events, which it signals with `SET LOCAL audit.scrub = 'on'`.
+CREATE FUNCTION audit_events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'UPDATE'
+    AND current_setting('audit.scrub', true) = 'on'
+    AND (NEW.id, NEW.occurred_at, NEW.actor_owner_id, NEW.actor_credential, NEW.action,
+         NEW.target_type, NEW.target_id, NEW.request_id)
+      IS NOT DISTINCT FROM
+        (OLD.id, OLD.occurred_at, OLD.actor_owner_id, OLD.actor_credential, OLD.action,
+         OLD.target_type, OLD.target_id, OLD.request_id)
+  THEN
+    RETURN NEW;
+  END IF;
+
+  RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+  BEFORE UPDATE OR DELETE ON audit_events
+  FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
@@ -0,0 +1,13 @@
+ALTER TABLE owners
+  ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE FUNCTION bump_version() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.version = OLD.version + 1;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER owners_bump_version
+  BEFORE UPDATE ON owners
+  FOR EACH ROW EXECUTE FUNCTION bump_version();
@@ -0,0 +1,3 @@
+-- Logins and password resets look owners up by email, so no two live owners may share one.
+-- Soft-deleted owners keep theirs until purged or erased, so they're left out.
+CREATE UNIQUE INDEX owners_email_unique_idx ON owners (lower(email)) WHERE deleted_at IS NULL;
@@ -0,0 +1,3 @@
+-- Some audited actions don't change a row, e.g. locking out a client IP or changing the log
+-- level. Those events are recorded with their target type alone.
+ALTER TABLE audit_events ALTER COLUMN target_id DROP NOT NULL;
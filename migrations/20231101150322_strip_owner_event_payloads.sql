@@ -0,0 +1,6 @@
+-- Owner events used to carry the whole owner, names and emails included, which outlived
+-- erasures. Keep only what current events carry: the id and version.
+UPDATE outbox
+SET payload = jsonb_strip_nulls(jsonb_build_object('id', aggregate_id, 'version', payload -> 'version'))
+WHERE aggregate_type = 'owner'
+  AND event_type IN ('owner.created', 'owner.updated');
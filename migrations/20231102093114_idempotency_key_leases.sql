@@ -0,0 +1,5 @@
+-- A claimed key is only held for as long as its lease; a request that never finished (client
+-- gone, handler panicked) doesn't block retries for the whole TTL.
+ALTER TABLE idempotency_keys ADD COLUMN locked_until TIMESTAMPTZ;
+
+UPDATE idempotency_keys SET locked_until = created_at + interval '1 minute' WHERE status_code IS NULL;
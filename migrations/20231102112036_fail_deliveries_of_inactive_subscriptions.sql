@@ -0,0 +1,5 @@
+-- Deliveries of inactive subscriptions are never attempted; deactivating one now fails them.
+UPDATE webhook_deliveries
+SET status = 'failed', last_error = 'Subscription was deactivated'
+WHERE status = 'pending'
+  AND subscription_id IN (SELECT id FROM webhook_subscriptions WHERE NOT active);
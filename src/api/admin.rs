@@ -0,0 +1,412 @@
+use crate::{
+    api::owner::{email_conflict, restore_owner, select_owner, select_owners, ApiPayload, Owners},
+    audit::{self, Actor, AuditEvent, AuditQuery, RequestContext},
+    auth::{require_permission, AuthenticatedOwner},
+    lockout::ThrottleKey,
+    logging::{self, LogLevel},
+    rbac::{self, Permission, Role},
+    AppState,
+};
+use axum::{
+    extract::{Path, Query as QueryParams},
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ListOwnersQuery {
+    #[serde(default)]
+    pub include_deleted: bool,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct UpdateLogLevel {
+    /// `tracing` filter directives, e.g. `info,proximity_service::api::owner=debug`.
+    pub directives: String,
+    /// Restores the configured directives after this many seconds.
+    pub revert_after_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct OwnerRolesResponse {
+    pub owner_id: i32,
+    pub roles: Vec<Role>,
+}
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+
+const MAX_PAGE_SIZE: u64 = 500;
+
+#[tracing::instrument(name = "List owners", skip(state))]
+pub async fn get_owners(
+    QueryParams(params): QueryParams<ListOwnersQuery>,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<Vec<Owners>>, (StatusCode, String)> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    match select_owners(
+        params.include_deleted,
+        limit,
+        params.offset.unwrap_or(0),
+        &state.db,
+    )
+    .await
+    {
+        Ok(owners) => Ok(Json(owners)),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Restore a soft-deleted owner", skip(state))]
+pub async fn post_restore_owner(
+    admin: AuthenticatedOwner,
+    context: RequestContext,
+    Path(id): Path<i32>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    match restore_owner(id, &Actor::owner(&admin, &context), &state.db).await {
+        Ok(0) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No deleted record found for id: {:?}", id),
+        )),
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        // Someone else signed up with the address while the owner was deleted.
+        Err(error) => Err(email_conflict(error)),
+    }
+}
+
+#[tracing::instrument(name = "Unlock an owner's account after failed logins", skip(state))]
+pub async fn delete_owner_lockout(
+    admin: AuthenticatedOwner,
+    context: RequestContext,
+    Path(id): Path<i32>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let owner = match select_owner(id, &state.db).await {
+        Ok(owner) => owner,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            ))
+        }
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
+    if state
+        .login_throttle
+        .reset(&ThrottleKey::account(&owner.email))
+    {
+        let event = AuditEvent {
+            action: "login_unlocked",
+            target_type: "owner",
+            target_id: Some(id),
+            diff: serde_json::json!({}),
+        };
+
+        if let Err(error) = audit::record(&Actor::owner(&admin, &context), event, &state.db).await {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ));
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tracing::instrument(name = "Unlock a client IP after failed logins", skip(state))]
+pub async fn delete_ip_lockout(
+    admin: AuthenticatedOwner,
+    context: RequestContext,
+    Path(ip): Path<IpAddr>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    if state.login_throttle.reset(&ThrottleKey::Ip(ip)) {
+        let event = AuditEvent {
+            action: "login_unlocked",
+            target_type: "client_ip",
+            target_id: None,
+            diff: audit::diff(Some(&serde_json::json!({ "ip": ip })), None),
+        };
+
+        if let Err(error) = audit::record(&Actor::owner(&admin, &context), event, &state.db).await {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ));
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tracing::instrument(name = "List an owner's roles", skip(state))]
+pub async fn get_owner_roles(
+    Path(id): Path<i32>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    if let Err(error) = select_owner(id, &state.db).await {
+        return Err(match error {
+            sqlx::Error::RowNotFound => (
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            ),
+            error => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ),
+        });
+    }
+
+    match rbac::select_owner_roles(id, &state.db).await {
+        Ok(roles) => Ok(Json(OwnerRolesResponse {
+            owner_id: id,
+            roles,
+        })),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Grant a role to an owner", skip(state))]
+pub async fn put_owner_role(
+    admin: AuthenticatedOwner,
+    context: RequestContext,
+    Path((id, role)): Path<(i32, Role)>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    if let Err(error) = select_owner(id, &state.db).await {
+        return Err(match error {
+            sqlx::Error::RowNotFound => (
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            ),
+            error => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ),
+        });
+    }
+
+    match grant_role(id, role, &Actor::owner(&admin, &context), &state.db).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Revoke a role from an owner", skip(state))]
+pub async fn delete_owner_role(
+    admin: AuthenticatedOwner,
+    context: RequestContext,
+    Path((id, role)): Path<(i32, Role)>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    // Keeps the last admin from locking everyone out of these routes by accident.
+    if id == admin.owner_id && role == Role::Admin {
+        return Err((
+            StatusCode::CONFLICT,
+            String::from("Admins can't revoke their own admin role"),
+        ));
+    }
+
+    match revoke_role(id, role, &Actor::owner(&admin, &context), &state.db).await {
+        Ok(0) => Err((
+            StatusCode::NOT_FOUND,
+            format!("Owner {:?} doesn't have the {} role", id, role),
+        )),
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Query the audit log", skip(state))]
+pub async fn get_audit_events(
+    QueryParams(params): QueryParams<AuditQuery>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    match audit::select_events(&params, limit, &state.db).await {
+        Ok(events) => Ok(Json(events)),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+/// The runtime log level, when this process' logging was set up by `telemetry::init`.
+fn log_level() -> Result<&'static LogLevel, (StatusCode, String)> {
+    logging::log_level().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        String::from("The log level can't be changed in this process"),
+    ))
+}
+
+#[tracing::instrument(name = "Read the log level")]
+pub async fn get_log_level() -> Result<impl IntoResponse, impl IntoResponse> {
+    log_level().map(|level| Json(level.status()))
+}
+
+#[tracing::instrument(name = "Change the log level", skip(state))]
+pub async fn put_log_level(
+    admin: AuthenticatedOwner,
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+    Json(body): Json<ApiPayload<UpdateLogLevel>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let level = log_level()?;
+
+    let update = body.payload;
+
+    let filter = EnvFilter::try_new(&update.directives).map_err(|error| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid directives {:?}: {}", update.directives, error),
+        )
+    })?;
+
+    let before = level.status();
+
+    let status = level
+        .set(filter, update.revert_after_secs.map(Duration::from_secs))
+        .map_err(|error| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )
+        })?;
+
+    let event = AuditEvent {
+        action: "log_level_changed",
+        target_type: "log_level",
+        target_id: None,
+        diff: audit::diff(
+            serde_json::to_value(&before).ok().as_ref(),
+            serde_json::to_value(&status).ok().as_ref(),
+        ),
+    };
+
+    match audit::record(&Actor::owner(&admin, &context), event, &state.db).await {
+        Ok(()) => Ok(Json(status)),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+/// Grants a role and audits it, unless the owner already had it.
+async fn grant_role(id: i32, role: Role, actor: &Actor, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let granted = rbac::grant_role(id, role, &mut tx).await?;
+
+    if granted > 0 {
+        audit::record(actor, role_change("role_granted", id, role, false), &mut tx).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(granted)
+}
+
+/// Revokes a role and audits it, if the owner had it.
+async fn revoke_role(id: i32, role: Role, actor: &Actor, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let revoked = rbac::revoke_role(id, role, &mut tx).await?;
+
+    if revoked > 0 {
+        audit::record(actor, role_change("role_revoked", id, role, true), &mut tx).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(revoked)
+}
+
+fn role_change(action: &'static str, id: i32, role: Role, had_role: bool) -> AuditEvent {
+    let role = serde_json::json!({ "role": role });
+
+    let (before, after) = if had_role {
+        (Some(&role), None)
+    } else {
+        (None, Some(&role))
+    };
+
+    AuditEvent {
+        action,
+        target_type: "owner",
+        target_id: Some(id),
+        diff: audit::diff(before, after),
+    }
+}
+
+fn guarded(permission: Permission, router: Router) -> Router {
+    router.route_layer(middleware::from_fn_with_state(
+        permission,
+        require_permission,
+    ))
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .merge(guarded(
+            Permission::ReadOwners,
+            Router::new()
+                .route("/admin/owner", get(get_owners))
+                .route("/admin/owner/:id/roles", get(get_owner_roles)),
+        ))
+        .merge(guarded(
+            Permission::DeleteOwners,
+            Router::new().route("/admin/owner/:id/restore", post(post_restore_owner)),
+        ))
+        .merge(guarded(
+            Permission::UnlockLogins,
+            Router::new()
+                .route("/admin/owner/:id/lockout", delete(delete_owner_lockout))
+                .route("/admin/lockout/ip/:ip", delete(delete_ip_lockout)),
+        ))
+        .merge(guarded(
+            Permission::ManageRoles,
+            Router::new().route(
+                "/admin/owner/:id/roles/:role",
+                put(put_owner_role).delete(delete_owner_role),
+            ),
+        ))
+        .merge(guarded(
+            Permission::ReadAuditLog,
+            Router::new().route("/admin/audit-events", get(get_audit_events)),
+        ))
+        .merge(guarded(
+            Permission::ManageLogging,
+            Router::new().route("/admin/log-level", get(get_log_level).put(put_log_level)),
+        ))
+}
@@ -0,0 +1,365 @@
+use crate::{
+    api::owner::{ApiPayload, OwnersIden},
+    auth::AuthenticatedOwner,
+    db,
+    rbac::{Permission, Scope},
+    redact, token, AppState,
+};
+use axum::{
+    extract::Path,
+    response::IntoResponse,
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use ipnetwork::IpNetwork;
+use rand::{rngs::OsRng, RngCore};
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, PgPool, Row};
+use std::{fmt, sync::Arc};
+
+#[derive(Iden)]
+pub enum ApiKeys {
+    Table,
+    Id,
+    OwnerId,
+    Name,
+    Prefix,
+    KeyHash,
+    Scopes,
+    AllowedIps,
+    ExpiresAt,
+    RevokedAt,
+    CreatedAt,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CreateApiKey {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub allowed_ips: Vec<IpNetwork>,
+}
+
+/// Returned once on creation. `key` is never stored and can't be retrieved again.
+#[derive(Deserialize, Serialize)]
+pub struct CreateApiKeyResponse {
+    pub id: i32,
+    pub key: String,
+    pub prefix: String,
+}
+
+impl fmt::Debug for CreateApiKeyResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "CreateApiKeyResponse", f)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ApiKey {
+    pub id: i32,
+    pub owner_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub allowed_ips: Vec<IpNetwork>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    /// Whether `ip` may use this key. An empty allowlist accepts any address.
+    pub fn allows_ip(&self, ip: std::net::IpAddr) -> bool {
+        self.allowed_ips.is_empty() || self.allowed_ips.iter().any(|net| net.contains(ip))
+    }
+}
+
+const API_KEY_COLUMNS: [ApiKeys; 9] = [
+    ApiKeys::Id,
+    ApiKeys::OwnerId,
+    ApiKeys::Name,
+    ApiKeys::Prefix,
+    ApiKeys::Scopes,
+    ApiKeys::AllowedIps,
+    ApiKeys::ExpiresAt,
+    ApiKeys::RevokedAt,
+    ApiKeys::CreatedAt,
+];
+
+fn api_key_from_row(row: PgRow) -> ApiKey {
+    let scopes: Vec<String> = row.get("scopes");
+
+    ApiKey {
+        id: row.get("id"),
+        owner_id: row.get("owner_id"),
+        name: row.get("name"),
+        prefix: row.get("prefix"),
+        // Scopes unknown to this build are dropped rather than failing authentication.
+        scopes: scopes
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect(),
+        allowed_ips: row.get("allowed_ips"),
+        expires_at: row.get("expires_at"),
+        revoked_at: row.get("revoked_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Keys look like `psk_<prefix>_<secret>`. Only the prefix is stored in clear so owners can
+/// tell their keys apart; lookups go through the hash of the whole key.
+fn generate_key() -> (String, String) {
+    let mut bytes = [0u8; 4];
+
+    OsRng.fill_bytes(&mut bytes);
+
+    let prefix = format!("psk_{}", hex::encode(bytes));
+
+    let key = format!("{}_{}", prefix, token::generate());
+
+    (prefix, key)
+}
+
+#[tracing::instrument(name = "CREATE an API key", skip(db))]
+pub async fn create_api_key(
+    owner_id: i32,
+    api_key: CreateApiKey,
+    db: &PgPool,
+) -> Result<CreateApiKeyResponse, sqlx::Error> {
+    let (prefix, key) = generate_key();
+
+    let scopes: Vec<String> = api_key
+        .scopes
+        .iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect();
+
+    let (sql, values) = Query::insert()
+        .into_table(ApiKeys::Table)
+        .columns([
+            ApiKeys::OwnerId,
+            ApiKeys::Name,
+            ApiKeys::Prefix,
+            ApiKeys::KeyHash,
+            ApiKeys::Scopes,
+            ApiKeys::AllowedIps,
+            ApiKeys::ExpiresAt,
+        ])
+        .values_panic([
+            owner_id.into(),
+            api_key.name.into(),
+            prefix.clone().into(),
+            token::hash(&key).into(),
+            scopes.into(),
+            api_key.allowed_ips.into(),
+            api_key.expires_at.into(),
+        ])
+        .returning(Query::returning().columns([ApiKeys::Id]))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(|row: PgRow| CreateApiKeyResponse {
+            id: row.get("id"),
+            key: key.clone(),
+            prefix: prefix.clone(),
+        })
+        .fetch_one(db)
+        .await
+}
+
+#[tracing::instrument(name = "SELECT an owner's API keys", skip(db))]
+pub async fn select_api_keys(owner_id: i32, db: &PgPool) -> Result<Vec<ApiKey>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(API_KEY_COLUMNS)
+        .from(ApiKeys::Table)
+        .and_where(Expr::col(ApiKeys::OwnerId).eq(owner_id))
+        .order_by(ApiKeys::Id, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(api_key_from_row)
+        .fetch_all(db)
+        .await
+}
+
+/// Keys that are usable: not revoked, not expired, and belonging to an owner that still exists.
+fn usable_api_keys() -> SelectStatement {
+    Query::select()
+        .columns(API_KEY_COLUMNS.map(|column| (ApiKeys::Table, column)))
+        .from(ApiKeys::Table)
+        .inner_join(
+            OwnersIden::Table,
+            Expr::col((OwnersIden::Table, OwnersIden::Id))
+                .equals((ApiKeys::Table, ApiKeys::OwnerId)),
+        )
+        .and_where(Expr::col((ApiKeys::Table, ApiKeys::RevokedAt)).is_null())
+        .cond_where(
+            Cond::any()
+                .add(Expr::col((ApiKeys::Table, ApiKeys::ExpiresAt)).is_null())
+                .add(Expr::col((ApiKeys::Table, ApiKeys::ExpiresAt)).gt(Expr::current_timestamp())),
+        )
+        .and_where(Expr::col((OwnersIden::Table, OwnersIden::DeletedAt)).is_null())
+        .to_owned()
+}
+
+/// Looks up a usable key by the key itself.
+#[tracing::instrument(name = "SELECT an API key by key", skip(key, db))]
+pub async fn find_api_key(key: &str, db: &PgPool) -> Result<Option<ApiKey>, sqlx::Error> {
+    let (sql, values) = usable_api_keys()
+        .and_where(Expr::col((ApiKeys::Table, ApiKeys::KeyHash)).eq(token::hash(key)))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(api_key_from_row)
+        .fetch_optional(db)
+        .await
+}
+
+/// Whether key `id` is still usable, for requests that outlast a single check.
+#[tracing::instrument(name = "SELECT whether an API key is usable", skip(db))]
+pub async fn api_key_is_usable(id: i32, db: &PgPool) -> Result<bool, sqlx::Error> {
+    let (sql, values) = usable_api_keys()
+        .and_where(Expr::col((ApiKeys::Table, ApiKeys::Id)).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .fetch_optional(db)
+        .await
+        .map(|row| row.is_some())
+}
+
+#[tracing::instrument(name = "REVOKE an API key", skip(db))]
+pub async fn revoke_api_key(owner_id: i32, id: i32, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let (sql, values) = Query::update()
+        .table(ApiKeys::Table)
+        .value(ApiKeys::RevokedAt, Expr::current_timestamp())
+        .and_where(Expr::col(ApiKeys::Id).eq(id))
+        .and_where(Expr::col(ApiKeys::OwnerId).eq(owner_id))
+        .and_where(Expr::col(ApiKeys::RevokedAt).is_null())
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+#[tracing::instrument(name = "Create an API key", skip(state, req))]
+pub async fn post_api_key(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<CreateApiKey>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_session()?;
+
+    owner.ensure_owner(id)?;
+
+    if req.payload.name.trim().is_empty() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("API key name must not be empty"),
+        ));
+    }
+
+    if req.payload.scopes.is_empty() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("API key must have at least one scope"),
+        ));
+    }
+
+    if matches!(req.payload.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("API key expiry must be in the future"),
+        ));
+    }
+
+    match create_api_key(id, req.payload, &state.db).await {
+        Ok(api_key) => {
+            tracing::warn!(
+                target: "audit",
+                event = "api_key_created",
+                owner_id = id,
+                api_key_id = api_key.id,
+                credential = %owner.credential,
+                "API key created"
+            );
+
+            Ok((StatusCode::CREATED, Json(api_key)))
+        }
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "List an owner's API keys", skip(state))]
+pub async fn get_api_keys(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_session()?;
+
+    owner.ensure_owner_or(id, Permission::ReadOwners)?;
+
+    match select_api_keys(id, &state.db).await {
+        Ok(api_keys) => Ok(Json(api_keys)),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Revoke an API key", skip(state))]
+pub async fn delete_api_key(
+    Path((id, key_id)): Path<(i32, i32)>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_session()?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    match revoke_api_key(id, key_id, &state.db).await {
+        Ok(0) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No active API key found for id: {:?}", key_id),
+        )),
+        Ok(_) => {
+            tracing::warn!(
+                target: "audit",
+                event = "api_key_revoked",
+                owner_id = id,
+                api_key_id = key_id,
+                revoked_by = owner.owner_id,
+                credential = %owner.credential,
+                "API key revoked"
+            );
+
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/owner/:id/api-keys", get(get_api_keys).post(post_api_key))
+        .route("/owner/:id/api-keys/:key_id", delete(delete_api_key))
+}
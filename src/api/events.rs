@@ -0,0 +1,190 @@
+use crate::{
+    auth::AuthenticatedOwner,
+    outbox::{self, DomainEvent},
+    rbac::{Permission, Scope},
+    AppState,
+};
+use axum::{
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Extension, Router,
+};
+use futures::{stream, Stream};
+use http::HeaderMap;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{self, Interval, MissedTickBehavior},
+};
+
+/// Most events replayed at once, on resume or after falling behind the live feed.
+const REPLAY_LIMIT: u64 = 1000;
+
+/// One client's view of the event feed: replayed events first, then live ones.
+struct Subscription {
+    owner: AuthenticatedOwner,
+    auth_check: Interval,
+    receiver: broadcast::Receiver<Arc<DomainEvent>>,
+    backlog: VecDeque<Arc<DomainEvent>>,
+    replayed: HashSet<i64>,
+    last_id: Option<i64>,
+    owner_id: Option<i32>,
+    db: PgPool,
+}
+
+impl Subscription {
+    fn visible(&self, event: &DomainEvent) -> bool {
+        event.aggregate_type == "owner"
+            && self
+                .owner_id
+                .is_none_or(|owner_id| owner_id == event.aggregate_id)
+    }
+
+    /// Queues the stored events after the last one sent. Events can show up both here and on
+    /// the live feed, so replayed ids are remembered and skipped there.
+    async fn replay(&mut self) -> Result<(), sqlx::Error> {
+        let Some(last_id) = self.last_id else {
+            return Ok(());
+        };
+
+        let events =
+            outbox::select_owner_events_after(last_id, self.owner_id, REPLAY_LIMIT, &self.db)
+                .await?;
+
+        self.replayed.extend(events.iter().map(|event| event.id));
+
+        self.backlog.extend(events.into_iter().map(Arc::new));
+
+        Ok(())
+    }
+
+    /// The next event to send, or `None` once the feed is gone or the credential the stream was
+    /// opened with stopped being valid.
+    async fn next(&mut self) -> Option<Arc<DomainEvent>> {
+        loop {
+            if let Some(event) = self.backlog.pop_front() {
+                return Some(event);
+            }
+
+            let received = tokio::select! {
+                _ = self.auth_check.tick() => {
+                    match self.owner.is_still_valid(&self.db).await {
+                        Ok(true) => continue,
+                        Ok(false) => {
+                            tracing::info!("Credential no longer valid, ending the event stream");
+
+                            return None;
+                        }
+                        Err(error) => {
+                            tracing::error!("Failed to check the credential: {:?}", error);
+
+                            return None;
+                        }
+                    }
+                }
+                received = self.receiver.recv() => received,
+            };
+
+            match received {
+                Ok(event) if self.visible(&event) && !self.replayed.contains(&event.id) => {
+                    return Some(event)
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        skipped,
+                        "Event stream fell behind, replaying from the outbox"
+                    );
+
+                    if let Err(error) = self.replay().await {
+                        tracing::error!("Failed to replay events: {:?}", error);
+
+                        return None;
+                    }
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Streams owner change events as Server-Sent Events, using the outbox id as the event id.
+///
+/// Owners see events about themselves, staff allowed to read owners see all of them. Clients
+/// reconnecting with `Last-Event-ID` first get what they missed, up to [`REPLAY_LIMIT`] events.
+/// The credential is checked again every `event_stream_auth_check_secs`, and the stream ends
+/// once it's no longer valid.
+#[tracing::instrument(name = "Stream owner events", skip(state, headers))]
+pub async fn get_event_stream(
+    owner: AuthenticatedOwner,
+    headers: HeaderMap,
+    state: Extension<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, serde_json::Error>>>, (StatusCode, String)> {
+    owner.require_scope(Scope::OwnerRead)?;
+
+    let last_id = match headers.get("last-event-id") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                .ok_or((
+                    StatusCode::BAD_REQUEST,
+                    String::from("Last-Event-ID must be an event id"),
+                ))?,
+        ),
+        None => None,
+    };
+
+    // Subscribe before replaying so nothing committed in between is missed.
+    let period = Duration::from_secs(state.config.event_stream_auth_check_secs);
+
+    let mut auth_check = time::interval_at(time::Instant::now() + period, period);
+
+    auth_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let owner_id = (!owner.has_permission(Permission::ReadOwners)).then_some(owner.owner_id);
+
+    let mut subscription = Subscription {
+        owner,
+        auth_check,
+        receiver: state.events.subscribe(),
+        backlog: VecDeque::new(),
+        replayed: HashSet::new(),
+        last_id,
+        owner_id,
+        db: state.db.clone(),
+    };
+
+    subscription.replay().await.map_err(|error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )
+    })?;
+
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+
+        subscription.last_id = Some(subscription.last_id.map_or(event.id, |id| id.max(event.id)));
+
+        let sse = Event::default()
+            .id(event.id.to_string())
+            .event(&event.event_type)
+            .json_data(&*event);
+
+        Some((sse, subscription))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+pub fn router() -> Router {
+    Router::new().route("/events/stream", get(get_event_stream))
+}
@@ -1,4 +1,195 @@
-use axum::{routing::get, Router};
+use crate::{
+    db,
+    telemetry::{self, ExporterState, TelemetryExporter},
+    AppState,
+};
+use axum::{response::IntoResponse, routing::get, Extension, Json, Router};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{migrate::Migrator, PgPool, Row};
+use std::{collections::BTreeMap, sync::Arc, time::Duration, time::Instant};
+use tokio::time::timeout;
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+    Failing,
+}
+
+/// Result of one readiness check. Only failing critical components make the service unready.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub critical: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub details: Value,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Readiness {
+    pub status: HealthStatus,
+    pub checks: BTreeMap<String, ComponentHealth>,
+}
+
+impl ComponentHealth {
+    fn ok(critical: bool, details: Value) -> Self {
+        Self {
+            status: HealthStatus::Ok,
+            critical,
+            message: None,
+            details,
+        }
+    }
+
+    fn not_ok(status: HealthStatus, critical: bool, message: String, details: Value) -> Self {
+        Self {
+            status,
+            critical,
+            message: Some(message),
+            details,
+        }
+    }
+}
+
+async fn check_database(db: &PgPool, limit: Duration) -> ComponentHealth {
+    let started = Instant::now();
+
+    let result = timeout(limit, db::query("SELECT 1").execute(db)).await;
+
+    let details = json!({ "latency_ms": started.elapsed().as_millis() as u64 });
+
+    match result {
+        Ok(Ok(_)) => ComponentHealth::ok(true, details),
+        Ok(Err(error)) => ComponentHealth::not_ok(
+            HealthStatus::Failing,
+            true,
+            format!("Query failed: {}", error),
+            details,
+        ),
+        Err(_) => ComponentHealth::not_ok(
+            HealthStatus::Failing,
+            true,
+            format!("No answer within {} ms", limit.as_millis()),
+            details,
+        ),
+    }
+}
+
+/// A pool with every connection checked out makes requests queue, but the instance still
+/// serves them, so saturation degrades rather than fails readiness.
+fn check_pool(db: &PgPool, max_connections: u32) -> ComponentHealth {
+    let size = db.size();
+
+    let idle = db.num_idle() as u32;
+
+    let in_use = size.saturating_sub(idle);
+
+    let details = json!({
+        "size": size,
+        "idle": idle,
+        "in_use": in_use,
+        "max_connections": max_connections,
+    });
+
+    if in_use >= max_connections {
+        ComponentHealth::not_ok(
+            HealthStatus::Degraded,
+            false,
+            String::from("All connections are in use"),
+            details,
+        )
+    } else {
+        ComponentHealth::ok(false, details)
+    }
+}
+
+/// Compares the migrations compiled into this build with the ones recorded as applied.
+async fn check_migrations(db: &PgPool, limit: Duration) -> ComponentHealth {
+    let query = db::query("SELECT version, success, checksum FROM _sqlx_migrations").fetch_all(db);
+
+    let rows = match timeout(limit, query).await {
+        Ok(Ok(rows)) => rows,
+        Ok(Err(error)) => {
+            return ComponentHealth::not_ok(
+                HealthStatus::Failing,
+                true,
+                format!("Failed to read applied migrations: {}", error),
+                Value::Null,
+            )
+        }
+        Err(_) => {
+            return ComponentHealth::not_ok(
+                HealthStatus::Failing,
+                true,
+                format!("No answer within {} ms", limit.as_millis()),
+                Value::Null,
+            )
+        }
+    };
+
+    let applied: BTreeMap<i64, (bool, Vec<u8>)> = rows
+        .iter()
+        .map(|row| {
+            (
+                row.get("version"),
+                (row.get("success"), row.get("checksum")),
+            )
+        })
+        .collect();
+
+    let mut pending = Vec::new();
+
+    let mut mismatched = Vec::new();
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        match applied.get(&migration.version) {
+            Some((true, checksum)) if checksum[..] == migration.checksum[..] => {}
+            Some((true, _)) => mismatched.push(migration.version),
+            _ => pending.push(migration.version),
+        }
+    }
+
+    let details = json!({
+        "applied": applied.len(),
+        "pending": pending,
+        "mismatched": mismatched,
+    });
+
+    if pending.is_empty() && mismatched.is_empty() {
+        ComponentHealth::ok(true, details)
+    } else {
+        ComponentHealth::not_ok(
+            HealthStatus::Failing,
+            true,
+            String::from("Database schema doesn't match this build"),
+            details,
+        )
+    }
+}
+
+/// Losing telemetry doesn't stop the service from answering requests.
+fn check_telemetry(exporter: TelemetryExporter) -> ComponentHealth {
+    match telemetry::exporter_state() {
+        ExporterState::Disabled => ComponentHealth::ok(false, json!({ "exporter": "disabled" })),
+        ExporterState::Healthy => ComponentHealth::ok(false, json!({ "exporter": exporter })),
+        ExporterState::Failing { error, at } => ComponentHealth::not_ok(
+            HealthStatus::Degraded,
+            false,
+            format!("Export failed: {}", error),
+            json!({ "exporter": exporter, "last_error_at": at }),
+        ),
+    }
+}
 
 #[tracing::instrument(name = "Health Check")]
 pub async fn get_health_check() -> Result<(), ()> {
@@ -6,6 +197,56 @@ pub async fn get_health_check() -> Result<(), ()> {
     Ok(())
 }
 
+/// Liveness: the process is up and serving requests. Never checks dependencies, so an outage
+/// elsewhere doesn't get the instance restarted.
+#[tracing::instrument(name = "Liveness Check")]
+pub async fn get_liveness() -> impl IntoResponse {
+    Json(json!({ "status": HealthStatus::Ok }))
+}
+
+/// Readiness: the instance can do useful work. Answers `503` when a critical check fails.
+#[tracing::instrument(name = "Readiness Check", skip(state))]
+pub async fn get_readiness(state: Extension<Arc<AppState>>) -> impl IntoResponse {
+    let limit = Duration::from_millis(state.config.health_check_timeout_ms);
+
+    let (database, migrations) = tokio::join!(
+        check_database(&state.db, limit),
+        check_migrations(&state.db, limit)
+    );
+
+    let checks = BTreeMap::from([
+        (String::from("database"), database),
+        (String::from("migrations"), migrations),
+        (
+            String::from("pool"),
+            check_pool(&state.db, state.config.db_max_connections),
+        ),
+        (
+            String::from("telemetry"),
+            check_telemetry(state.config.telemetry_exporter),
+        ),
+    ]);
+
+    let status = checks
+        .values()
+        .map(|check| match check.status {
+            HealthStatus::Failing if !check.critical => HealthStatus::Degraded,
+            status => status,
+        })
+        .max()
+        .unwrap_or(HealthStatus::Ok);
+
+    let code = match status {
+        HealthStatus::Failing => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+
+    (code, Json(Readiness { status, checks }))
+}
+
 pub fn router() -> Router {
-    Router::new().route("/health_check", get(get_health_check))
+    Router::new()
+        .route("/health_check", get(get_health_check))
+        .route("/health/live", get(get_liveness))
+        .route("/health/ready", get(get_readiness))
 }
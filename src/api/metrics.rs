@@ -0,0 +1,36 @@
+use crate::telemetry;
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use hyper::StatusCode;
+use prometheus::{Encoder, TextEncoder};
+
+/// Serves the metrics in the Prometheus text exposition format. Answers `404` unless
+/// `metrics_exporter` is `prometheus`, so the endpoint doesn't exist where metrics are pushed.
+#[tracing::instrument(name = "Scrape metrics")]
+pub async fn get_metrics() -> Result<impl IntoResponse, (StatusCode, String)> {
+    let exporter = telemetry::prometheus_exporter().ok_or((
+        StatusCode::NOT_FOUND,
+        String::from("Prometheus metrics are disabled"),
+    ))?;
+
+    let encoder = TextEncoder::new();
+
+    let mut body = Vec::new();
+
+    encoder
+        .encode(&exporter.registry().gather(), &mut body)
+        .map_err(|error| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )
+        })?;
+
+    Ok((
+        [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+        body,
+    ))
+}
+
+pub fn router() -> Router {
+    Router::new().route("/metrics", get(get_metrics))
+}
@@ -1,3 +1,21 @@
+pub mod admin;
+
+pub mod api_key;
+
+pub mod events;
+
 pub mod health_check;
 
+pub mod metrics;
+
 pub mod owner;
+
+pub mod password_reset;
+
+pub mod privacy;
+
+pub mod session;
+
+pub mod verification;
+
+pub mod webhook;
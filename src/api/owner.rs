@@ -1,16 +1,33 @@
-use crate::AppState;
+use crate::{
+    api::{
+        privacy::erase_owner, session::revoke_other_sessions,
+        verification::send_verification_email, webhook,
+    },
+    audit::{self, Actor, AuditEvent, RequestContext},
+    auth::AuthenticatedOwner,
+    conditional::{etag, IfMatch, IfNoneMatch},
+    db, idempotency,
+    merge_patch::MergePatch,
+    outbox, password,
+    rbac::{self, Permission, Role, Scope},
+    redact, AppState,
+};
 use axum::{
-    extract::Path,
+    extract::{Path, Query as QueryParams},
+    middleware,
     response::{IntoResponse, Response},
     routing::{delete, get, patch, post},
     Extension, Json, Router,
 };
+use chrono::{DateTime, Duration, Utc};
+use http::header::ETAG;
 use hyper::StatusCode;
 use sea_query::*;
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgRow, PgPool, Row};
-use std::sync::Arc;
+use serde_json::Value;
+use sqlx::{postgres::PgRow, PgPool, Postgres, Row, Transaction};
+use std::{fmt, sync::Arc};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ApiPayload<T> {
@@ -23,16 +40,30 @@ pub struct Owners {
     pub id: i32,
     pub name: String,
     pub email: String,
+    #[serde(skip_serializing, default)]
     pub password: String,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub erased_at: Option<DateTime<Utc>>,
+    /// Bumped on every update; sent as the `ETag`.
+    pub version: i32,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize)]
 pub struct CreateOwner {
     pub name: String,
     pub email: String,
     pub password: String,
 }
 
+impl fmt::Debug for CreateOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "CreateOwner", f)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct UpdateProfile {
     pub name: String,  // NOTE: Is this the appropriate type?
@@ -40,10 +71,27 @@ pub struct UpdateProfile {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct UpdateCredentials {
+pub struct UpdateEmail {
     pub email: String,
-    pub password: String,
-    pub owner_id: i32, // NOTE: Why does u32 not work for making the sql query
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UpdatePassword {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+impl fmt::Debug for UpdatePassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "UpdatePassword", f)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DeleteOwnerQuery {
+    /// Irreversibly anonymize the owner instead of soft-deleting it.
+    #[serde(default)]
+    pub erase: bool,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -51,11 +99,13 @@ pub struct CreateOwnerResponse {
     pub id: i32,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Serialize)]
 pub struct ErrorResponse {
     pub message: String,
 }
 
+#[allow(dead_code)]
 struct AppError(anyhow::Error);
 
 // Tell axum how to convert `AppError` into a response.
@@ -69,42 +119,321 @@ impl IntoResponse for AppError {
     }
 }
 
+/// Fields a merge patch may change, with their columns. All of them hold strings.
+const PATCHABLE_FIELDS: [(&str, OwnersIden); 2] =
+    [("name", OwnersIden::Name), ("email", OwnersIden::Email)];
+
+pub(crate) const OWNER_COLUMNS: [OwnersIden; 10] = [
+    OwnersIden::Id,
+    OwnersIden::Name,
+    OwnersIden::Email,
+    OwnersIden::Password,
+    OwnersIden::EmailVerifiedAt,
+    OwnersIden::CreatedAt,
+    OwnersIden::UpdatedAt,
+    OwnersIden::DeletedAt,
+    OwnersIden::ErasedAt,
+    OwnersIden::Version,
+];
+
+pub(crate) fn owner_from_row(row: PgRow) -> Owners {
+    Owners {
+        id: row.get("id"),
+        name: row.get("name"),
+        email: row.get("email"),
+        password: row.get("password"),
+        email_verified_at: row.get("email_verified_at"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deleted_at: row.get("deleted_at"),
+        erased_at: row.get("erased_at"),
+        version: row.get("version"),
+    }
+}
+
+/// An owner as recorded in the audit log. The password hash is kept, unlike in API responses,
+/// so that [`audit::diff`] can tell it changed; the diff redacts its value.
+fn audit_snapshot(owner: &Owners) -> Value {
+    let mut snapshot = serde_json::to_value(owner).unwrap_or_default();
+
+    if let Value::Object(fields) = &mut snapshot {
+        fields.insert(
+            String::from("password"),
+            Value::from(owner.password.as_str()),
+        );
+    }
+
+    snapshot
+}
+
+/// Records a change to an owner in the audit log and queues the matching domain event, both in
+/// the change's transaction. `None` stands for an owner that didn't exist before or doesn't
+/// exist after.
+///
+/// Events follow what API clients can see: an owner appearing (created or restored) is
+/// `owner.created`, one disappearing (deleted or erased) is `owner.deleted`, and any other
+/// change to a visible owner is `owner.updated`. Payloads carry the owner's id, version and the
+/// names of the fields that changed, never their values: events outlive erasures, so consumers
+/// fetch the owner for its current data.
+async fn record_owner_change(
+    id: i32,
+    action: &'static str,
+    before: Option<&Owners>,
+    after: Option<&Owners>,
+    actor: &Actor,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    let diff = audit::diff(
+        before.map(audit_snapshot).as_ref(),
+        after.map(audit_snapshot).as_ref(),
+    );
+
+    // Bookkeeping that changes with every update.
+    let changed: Vec<&String> = diff
+        .as_object()
+        .into_iter()
+        .flat_map(|fields| fields.keys())
+        .filter(|field| !["version", "updated_at"].contains(&field.as_str()))
+        .collect();
+
+    let visible = |owner: Option<&Owners>| owner.is_some_and(|owner| owner.deleted_at.is_none());
+
+    let event = match (visible(before), visible(after), after) {
+        (false, true, Some(after)) => Some((
+            "owner.created",
+            serde_json::json!({ "id": id, "version": after.version }),
+        )),
+        (true, true, Some(after)) => Some((
+            "owner.updated",
+            serde_json::json!({ "id": id, "version": after.version, "changed": changed }),
+        )),
+        (true, false, _) => Some(("owner.deleted", serde_json::json!({ "id": id }))),
+        _ => None,
+    };
+
+    let audit_event = AuditEvent {
+        action,
+        target_type: "owner",
+        target_id: Some(id),
+        diff,
+    };
+
+    audit::record(actor, audit_event, &mut *tx).await?;
+
+    let Some((event_type, payload)) = event else {
+        return Ok(());
+    };
+
+    let event_id = outbox::enqueue("owner", id, event_type, payload, &mut *tx).await?;
+
+    webhook::fan_out(event_id, id, event_type, &mut *tx)
+        .await
+        .map(|_| ())
+}
+
+/// Runs `update` against owner `id` and records the change as `action` in the same
+/// transaction. The row is locked first so the recorded diff matches what was written.
+/// Returns the updated owner, or `None` when `update` matched nothing.
+pub(crate) async fn update_owner_audited(
+    id: i32,
+    update: &mut UpdateStatement,
+    action: &'static str,
+    actor: &Actor,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Owners>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(OWNER_COLUMNS)
+        .from(OwnersIden::Table)
+        .and_where(Expr::col(OwnersIden::Id).eq(id))
+        .lock(LockType::Update)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let before = db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let (sql, values) = update
+        .and_where(Expr::col(OwnersIden::Id).eq(id))
+        .returning(Query::returning().columns(OWNER_COLUMNS))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let Some(after) = db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    record_owner_change(id, action, before.as_ref(), Some(&after), actor, tx).await?;
+
+    Ok(Some(after))
+}
+
+/// Limits `update` to the versions accepted by an `If-Match` header; `None` accepts any.
+fn matching_version(
+    update: &mut UpdateStatement,
+    versions: Option<Vec<i32>>,
+) -> &mut UpdateStatement {
+    update.and_where_option(versions.map(|versions| Expr::col(OwnersIden::Version).is_in(versions)))
+}
+
+/// Unique index on live owners' lowercased emails.
+const UNIQUE_EMAIL_INDEX: &str = "owners_email_unique_idx";
+
+/// Rejects what can't be an email address: exactly one `@`, something before it, a dotted
+/// domain after it and no whitespace. Deliverability is left to email verification.
+pub(crate) fn validate_email(email: &str) -> Result<(), (StatusCode, String)> {
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+
+    if valid && email.len() <= 254 {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Invalid email address: {:?}", email),
+        ))
+    }
+}
+
+/// Rejects passwords shorter than [`password::MIN_PASSWORD_LENGTH`].
+pub(crate) fn validate_password(password: &str) -> Result<(), (StatusCode, String)> {
+    if password.len() < password::MIN_PASSWORD_LENGTH {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "Password must be at least {} characters long",
+                password::MIN_PASSWORD_LENGTH
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Answers a write that failed because another live owner already uses the email with `409`.
+pub(crate) fn email_conflict(error: sqlx::Error) -> (StatusCode, String) {
+    match error {
+        sqlx::Error::Database(error) if error.constraint() == Some(UNIQUE_EMAIL_INDEX) => (
+            StatusCode::CONFLICT,
+            String::from("Email address is already in use"),
+        ),
+        error => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        ),
+    }
+}
+
+/// Explains why a conditional update of owner `id` changed nothing: either it's gone, or it
+/// was modified since the client read it.
+async fn unmatched_update(id: i32, db: &PgPool) -> (StatusCode, String) {
+    match select_owner(id, db).await {
+        Ok(_) => (
+            StatusCode::PRECONDITION_FAILED,
+            format!("Record was modified since it was read for id: {:?}", id),
+        ),
+        Err(sqlx::Error::RowNotFound) => (
+            StatusCode::NOT_FOUND,
+            format!("Record not found for id: {:?}", id),
+        ),
+        Err(error) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        ),
+    }
+}
+
 #[tracing::instrument(name = "SELECT a single owner")]
 pub async fn select_owner(id: i32, db: &PgPool) -> Result<Owners, sqlx::Error> {
     let (sql, values) = Query::select()
-        .columns([
-            OwnersIden::Id,
-            OwnersIden::Name,
-            OwnersIden::Email,
-            OwnersIden::Password,
-        ])
+        .columns(OWNER_COLUMNS)
         .from(OwnersIden::Table)
         .and_where(Expr::col(OwnersIden::Id).eq(id))
+        .and_where(Expr::col(OwnersIden::DeletedAt).is_null())
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .map(|row: PgRow| Owners {
-            id: row.get("id"),
-            name: row.get("name"),
-            email: row.get("email"),
-            password: row.get("password"),
-        })
+    db::query_with(&sql, values)
+        .map(owner_from_row)
         .fetch_one(db)
         .await
-        .map_err(|error| {
-            tracing::error!("Failed to execute query: {:?}", error);
-            error
-        })
+}
+
+/// Matches regardless of case, like the unique index on emails.
+#[tracing::instrument(name = "SELECT a single owner by email", skip(email))]
+pub async fn select_owner_by_email(email: &str, db: &PgPool) -> Result<Owners, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(OWNER_COLUMNS)
+        .from(OwnersIden::Table)
+        .and_where(Expr::expr(Func::lower(Expr::col(OwnersIden::Email))).eq(email.to_lowercase()))
+        .and_where(Expr::col(OwnersIden::DeletedAt).is_null())
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_one(db)
+        .await
+}
+
+/// Pages through owners ordered by id, optionally including soft-deleted ones.
+#[tracing::instrument(name = "SELECT a page of owners")]
+pub async fn select_owners(
+    include_deleted: bool,
+    limit: u64,
+    offset: u64,
+    db: &PgPool,
+) -> Result<Vec<Owners>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(OWNER_COLUMNS)
+        .from(OwnersIden::Table)
+        .conditions(
+            include_deleted,
+            |_| {},
+            |query| {
+                query.and_where(Expr::col(OwnersIden::DeletedAt).is_null());
+            },
+        )
+        .order_by(OwnersIden::Id, Order::Asc)
+        .limit(limit)
+        .offset(offset)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_all(db)
+        .await
 }
 
 // Pattern: Error handling
-#[tracing::instrument(name = "GET a single Owner resource")]
+#[tracing::instrument(name = "GET a single Owner resource", skip(state))]
 pub async fn get_owner(
     Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    if_none_match: IfNoneMatch,
     state: Extension<Arc<AppState>>,
 ) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::OwnerRead)?;
+
+    owner.ensure_owner_or(id, Permission::ReadOwners)?;
+
     match select_owner(id, &state.db).await {
-        Ok(record) => Ok((StatusCode::OK, Json(record))),
+        Ok(record) if if_none_match.matches(record.version) => {
+            Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag(record.version))]).into_response())
+        }
+        Ok(record) => {
+            Ok((StatusCode::OK, [(ETAG, etag(record.version))], Json(record)).into_response())
+        }
         Err(sqlx::Error::RowNotFound) => Err((
             StatusCode::NOT_FOUND,
             format!("Record not found for id: {:?}", id),
@@ -116,44 +445,146 @@ pub async fn get_owner(
     }
 }
 
-#[tracing::instrument(name = "CREATE a single owner")]
+/// Inserts an owner holding the `owner` role. `owner.password` must already be hashed with
+/// [`password::hash`].
+#[tracing::instrument(name = "CREATE a single owner", skip(db))]
 pub async fn create_owner(
     owner: CreateOwner,
+    actor: &Actor,
     db: &PgPool,
 ) -> Result<CreateOwnerResponse, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
     let (sql, values) = Query::insert()
         .into_table(OwnersIden::Table)
         .columns([OwnersIden::Name, OwnersIden::Email, OwnersIden::Password])
         .values_panic([owner.name.into(), owner.email.into(), owner.password.into()])
-        .returning(Query::returning().columns([OwnersIden::Id]))
+        .returning(Query::returning().columns(OWNER_COLUMNS))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .map(|row: PgRow| CreateOwnerResponse { id: row.get("id") })
-        .fetch_one(db)
-        .await
-        .map_err(|error| {
-            tracing::error!("Failed to execute query: {:?}", error);
-            error
-        })
+    let created = db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_one(&mut tx)
+        .await?;
+
+    rbac::grant_role(created.id, Role::Owner, &mut tx).await?;
+
+    record_owner_change(
+        created.id,
+        "owner_created",
+        None,
+        Some(&created),
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(CreateOwnerResponse { id: created.id })
 }
 
-#[tracing::instrument(name = "POST a single Owner resource")]
+#[tracing::instrument(name = "POST a single Owner resource", skip(state))]
 pub async fn post_owner(
+    context: RequestContext,
     state: Extension<Arc<AppState>>,
     Json(req): Json<ApiPayload<CreateOwner>>,
 ) -> impl IntoResponse {
+    validate_email(&req.payload.email)?;
+
+    validate_password(&req.payload.password)?;
+
+    let password = match password::hash(req.payload.password).await {
+        Ok(hash) => hash,
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
     let owner = CreateOwner {
         name: req.payload.name,
-        email: req.payload.email,
-        password: req.payload.password,
+        email: req.payload.email.clone(),
+        password,
     };
 
-    match create_owner(owner, &state.db).await {
-        Ok(record) => Ok((
-            StatusCode::CREATED,
-            Json(CreateOwnerResponse { id: record.id }),
-        )),
+    match create_owner(owner, &Actor::anonymous(&context), &state.db).await {
+        Ok(record) => {
+            if let Err(error) = send_verification_email(record.id, &req.payload.email, &state).await
+            {
+                tracing::error!("Failed to send verification email: {:?}", error);
+            }
+
+            Ok((
+                StatusCode::CREATED,
+                Json(CreateOwnerResponse { id: record.id }),
+            ))
+        }
+        Err(error) => Err(email_conflict(error)),
+    }
+}
+
+#[tracing::instrument(name = "UPDATE an owner's profile", skip(db))]
+pub async fn update_owner_profile(
+    id: i32,
+    name: &str,
+    versions: Option<Vec<i32>>,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<Owners>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let updated = update_owner_audited(
+        id,
+        matching_version(
+            Query::update()
+                .table(OwnersIden::Table)
+                .value(OwnersIden::Name, name)
+                .and_where(Expr::col(OwnersIden::DeletedAt).is_null()),
+            versions,
+        ),
+        "owner_profile_updated",
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(updated)
+}
+
+#[tracing::instrument(name = "Update an Owner's profile", skip(state))]
+pub async fn update_profile(
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    if_match: IfMatch,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(req): Json<ApiPayload<UpdateProfile>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if req.payload.owner_id != id {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("owner_id doesn't match the owner in the URL"),
+        ));
+    }
+
+    owner.require_scope(Scope::OwnerWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    let versions = if_match.require()?;
+
+    let actor = Actor::owner(&owner, &context);
+
+    match update_owner_profile(id, &req.payload.name, versions, &actor, &state.db).await {
+        Ok(Some(updated)) => Ok((StatusCode::NO_CONTENT, [(ETAG, etag(updated.version))])),
+        Ok(None) => Err(unmatched_update(id, &state.db).await),
         Err(error) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Unknown Error: {:?}", error),
@@ -161,57 +592,492 @@ pub async fn post_owner(
     }
 }
 
-#[tracing::instrument(name = "Update an Owner's profile")]
-pub async fn update_profile(
-    state: Extension<Arc<AppState>>,
-    Json(req): Json<ApiPayload<UpdateProfile>>,
-) -> impl IntoResponse {
-    let _patch = sqlx::query_scalar!(
-        r#"UPDATE owners SET name = $1 WHERE id = $2;"#,
-        req.payload.name,
-        req.payload.owner_id
+/// Changes an owner's email and clears its verification, since the new address is unproven.
+#[tracing::instrument(name = "UPDATE an owner's email", skip(email, db))]
+pub async fn update_owner_email(
+    id: i32,
+    email: &str,
+    versions: Option<Vec<i32>>,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<Owners>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let updated = update_owner_audited(
+        id,
+        matching_version(
+            Query::update()
+                .table(OwnersIden::Table)
+                .value(OwnersIden::Email, email)
+                .value(OwnersIden::EmailVerifiedAt, Option::<DateTime<Utc>>::None)
+                .and_where(Expr::col(OwnersIden::DeletedAt).is_null()),
+            versions,
+        ),
+        "owner_email_updated",
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(updated)
+}
+
+/// Stores a new password hash and revokes every session except `keep_session_id`.
+#[tracing::instrument(name = "UPDATE an owner's password", skip(password_hash, db))]
+pub async fn update_owner_password(
+    id: i32,
+    password_hash: String,
+    keep_session_id: i32,
+    versions: Option<Vec<i32>>,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<Owners>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let Some(updated) = update_owner_audited(
+        id,
+        matching_version(
+            Query::update()
+                .table(OwnersIden::Table)
+                .value(OwnersIden::Password, password_hash)
+                .and_where(Expr::col(OwnersIden::DeletedAt).is_null()),
+            versions,
+        ),
+        "owner_password_changed",
+        actor,
+        &mut tx,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    revoke_other_sessions(id, keep_session_id, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(Some(updated))
+}
+
+/// Sets each of `changes` on owner `id`. A new email clears the owner's verification.
+#[tracing::instrument(name = "UPDATE an owner's fields", skip(changes, db))]
+pub async fn update_owner_fields(
+    id: i32,
+    changes: Vec<(OwnersIden, String)>,
+    versions: Option<Vec<i32>>,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<Owners>, sqlx::Error> {
+    let mut update = Query::update();
+
+    update
+        .table(OwnersIden::Table)
+        .and_where(Expr::col(OwnersIden::DeletedAt).is_null());
+
+    for (column, value) in changes {
+        if column == OwnersIden::Email {
+            update.value(OwnersIden::EmailVerifiedAt, Option::<DateTime<Utc>>::None);
+        }
+
+        update.value(column, value);
+    }
+
+    let mut tx = db.begin().await?;
+
+    let updated = update_owner_audited(
+        id,
+        matching_version(&mut update, versions),
+        "owner_updated",
+        actor,
+        &mut tx,
     )
-    .fetch_one(&state.db)
-    .await;
+    .await?;
 
-    StatusCode::NO_CONTENT
+    tx.commit().await?;
+
+    Ok(updated)
 }
 
-// TODO: Obfuscate password and possibly email
-#[tracing::instrument(name = "Update an Owner's credentials")]
-pub async fn update_credentials(
+/// Applies a JSON Merge Patch to an owner's representation. Only `name` and `email` may
+/// change; the rest of the document must be left as it is.
+#[tracing::instrument(name = "Patch a single Owner resource", skip(state, patch))]
+pub async fn patch_owner(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    if_match: IfMatch,
     state: Extension<Arc<AppState>>,
-    Json(req): Json<ApiPayload<UpdateCredentials>>,
-) -> impl IntoResponse {
-    let _patch = sqlx::query_scalar!(
-        r#"UPDATE owners SET email = $1, password = $2 WHERE id = $3;"#,
-        req.payload.email,
-        req.payload.password,
-        req.payload.owner_id,
+    patch: MergePatch,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    owner.require_scope(Scope::OwnerWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    let versions = if_match.require()?;
+
+    let current = match select_owner(id, &state.db).await {
+        Ok(current) => current,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            ))
+        }
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
+    // Checked up front as well, since a patch that changes nothing never reaches the UPDATE.
+    if matches!(&versions, Some(versions) if !versions.contains(&current.version)) {
+        return Err((
+            StatusCode::PRECONDITION_FAILED,
+            format!("Record was modified since it was read for id: {:?}", id),
+        ));
+    }
+
+    let original = serde_json::to_value(&current).unwrap_or_default();
+
+    let mut merged = original.clone();
+
+    patch.apply(&mut merged);
+
+    let fields: Vec<&String> = match (&original, &merged) {
+        (Value::Object(original), Value::Object(merged)) => {
+            original.keys().chain(merged.keys()).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    if let Some(field) = fields.into_iter().find(|field| {
+        original.get(field) != merged.get(field)
+            && !PATCHABLE_FIELDS.iter().any(|(name, _)| name == field)
+    }) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Field `{}` can't be changed", field),
+        ));
+    }
+
+    let patched: Owners = serde_json::from_value(merged.clone()).map_err(|error| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Invalid owner: {}", error),
+        )
+    })?;
+
+    if patched.name.trim().is_empty() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("Owner name must not be empty"),
+        ));
+    }
+
+    if patched.email != current.email {
+        validate_email(&patched.email)?;
+    }
+
+    let changes: Vec<(OwnersIden, String)> = PATCHABLE_FIELDS
+        .iter()
+        .filter(|(field, _)| original.get(field) != merged.get(field))
+        .filter_map(|(field, column)| Some((*column, merged.get(field)?.as_str()?.to_string())))
+        .collect();
+
+    if changes.is_empty() {
+        return Ok((
+            StatusCode::OK,
+            [(ETAG, etag(current.version))],
+            Json(current),
+        ));
+    }
+
+    let email_changed = changes
+        .iter()
+        .any(|(column, _)| *column == OwnersIden::Email);
+
+    let actor = Actor::owner(&owner, &context);
+
+    let updated = match update_owner_fields(id, changes, versions, &actor, &state.db).await {
+        Ok(Some(updated)) => updated,
+        Ok(None) => return Err(unmatched_update(id, &state.db).await),
+        Err(error) => return Err(email_conflict(error)),
+    };
+
+    if email_changed {
+        if let Err(error) = send_verification_email(id, &updated.email, &state).await {
+            tracing::error!("Failed to send verification email: {:?}", error);
+        }
+    }
+
+    Ok((
+        StatusCode::OK,
+        [(ETAG, etag(updated.version))],
+        Json(updated),
+    ))
+}
+
+#[tracing::instrument(name = "Update an Owner's email", skip(state, req))]
+pub async fn update_email(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    if_match: IfMatch,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<UpdateEmail>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::OwnerWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    let versions = if_match.require()?;
+
+    validate_email(&req.payload.email)?;
+
+    let actor = Actor::owner(&owner, &context);
+
+    let updated =
+        match update_owner_email(id, &req.payload.email, versions, &actor, &state.db).await {
+            Ok(Some(updated)) => updated,
+            Ok(None) => return Err(unmatched_update(id, &state.db).await),
+            Err(error) => return Err(email_conflict(error)),
+        };
+
+    if let Err(error) = send_verification_email(id, &req.payload.email, &state).await {
+        tracing::error!("Failed to send verification email: {:?}", error);
+    }
+
+    Ok((StatusCode::NO_CONTENT, [(ETAG, etag(updated.version))]))
+}
+
+#[tracing::instrument(name = "Update an Owner's password", skip(state, req))]
+pub async fn update_password(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    if_match: IfMatch,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<UpdatePassword>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let session_id = owner.require_session()?;
+
+    owner.ensure_owner(id)?;
+
+    let versions = if_match.require()?;
+
+    validate_password(&req.payload.new_password)?;
+
+    let record = match select_owner(id, &state.db).await {
+        Ok(record) => record,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            ))
+        }
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
+    match password::verify(req.payload.current_password, record.password).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                String::from("Current password is incorrect"),
+            ))
+        }
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    }
+
+    let password_hash = match password::hash(req.payload.new_password).await {
+        Ok(hash) => hash,
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
+    let actor = Actor::owner(&owner, &context);
+
+    match update_owner_password(id, password_hash, session_id, versions, &actor, &state.db).await {
+        Ok(Some(updated)) => Ok((StatusCode::NO_CONTENT, [(ETAG, etag(updated.version))])),
+        Ok(None) => Err(unmatched_update(id, &state.db).await),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "SOFT DELETE a single owner", skip(db))]
+pub async fn soft_delete_owner(id: i32, actor: &Actor, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let deleted = update_owner_audited(
+        id,
+        Query::update()
+            .table(OwnersIden::Table)
+            .value(OwnersIden::DeletedAt, Expr::current_timestamp())
+            .and_where(Expr::col(OwnersIden::DeletedAt).is_null()),
+        "owner_deleted",
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(u64::from(deleted.is_some()))
+}
+
+#[tracing::instrument(name = "RESTORE a single soft-deleted owner", skip(db))]
+pub async fn restore_owner(id: i32, actor: &Actor, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let restored = update_owner_audited(
+        id,
+        Query::update()
+            .table(OwnersIden::Table)
+            .value(OwnersIden::DeletedAt, Option::<DateTime<Utc>>::None)
+            .and_where(Expr::col(OwnersIden::DeletedAt).is_not_null())
+            .and_where(Expr::col(OwnersIden::ErasedAt).is_null()),
+        "owner_restored",
+        actor,
+        &mut tx,
     )
-    .fetch_one(&state.db)
-    .await;
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(u64::from(restored.is_some()))
+}
+
+/// Permanently removes owners that were soft-deleted more than `retention_days` ago.
+///
+/// Their earlier audit events are scrubbed along with them, leaving only which fields changed.
+#[tracing::instrument(name = "PURGE soft-deleted owners", skip(db))]
+pub async fn purge_deleted_owners(retention_days: i64, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let cutoff = Utc::now() - Duration::days(retention_days);
+
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::delete()
+        .from_table(OwnersIden::Table)
+        .and_where(Expr::col(OwnersIden::DeletedAt).lt(cutoff))
+        .returning(Query::returning().columns(OWNER_COLUMNS))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let purged = db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_all(&mut tx)
+        .await?;
+
+    for owner in &purged {
+        record_owner_change(
+            owner.id,
+            "owner_purged",
+            Some(owner),
+            None,
+            &Actor::system(),
+            &mut tx,
+        )
+        .await?;
+
+        audit::scrub("owner", owner.id, &mut tx).await?;
+    }
+
+    tx.commit().await?;
 
-    StatusCode::NO_CONTENT
+    Ok(purged.len() as u64)
 }
 
-#[tracing::instrument(name = "Delete a single owner record")]
+#[tracing::instrument(name = "Delete a single owner record", skip(state))]
 pub async fn delete_owner(
     Path(id): Path<i32>,
+    QueryParams(params): QueryParams<DeleteOwnerQuery>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
     state: Extension<Arc<AppState>>,
-) -> impl IntoResponse {
-    let _delete = sqlx::query_scalar!(r#"delete from "owners" where id = $1"#, id,)
-        .fetch_one(&state.db)
-        .await;
+) -> Result<StatusCode, (StatusCode, String)> {
+    owner.require_scope(Scope::OwnerWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::DeleteOwners, &state.db)
+        .await?;
+
+    let actor = Actor::owner(&owner, &context);
+
+    if params.erase {
+        // Too destructive to hand to machine clients.
+        owner.require_session()?;
 
-    StatusCode::NO_CONTENT
+        return match erase_owner(id, &actor, &state.db).await {
+            Ok(true) => {
+                tracing::warn!(
+                    target: "audit",
+                    event = "owner_erased",
+                    owner_id = id,
+                    requested_by = owner.owner_id,
+                    credential = %owner.credential,
+                    "owner erased"
+                );
+
+                Ok(StatusCode::NO_CONTENT)
+            }
+            Ok(false) => Err((
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            )),
+            Err(error) => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )),
+        };
+    }
+
+    match soft_delete_owner(id, &actor, &state.db).await {
+        Ok(0) => Err((
+            StatusCode::NOT_FOUND,
+            format!("Record not found for id: {:?}", id),
+        )),
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
 }
 
 pub fn router() -> Router {
     Router::new()
         .route("/owner/:id", get(get_owner))
-        .route("/owner", post(post_owner))
+        .route(
+            "/owner",
+            post(post_owner).layer(middleware::from_fn(idempotency::middleware)),
+        )
         .route("/owner/:id", delete(delete_owner))
+        .route("/owner/:id", patch(patch_owner))
         .route("/owner/:id/profile", patch(update_profile))
-        .route("/owner/:id/credentials", patch(update_credentials))
+        .route("/owner/:id/email", patch(update_email))
+        .route("/owner/:id/password", patch(update_password))
 }
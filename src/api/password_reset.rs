@@ -0,0 +1,221 @@
+use crate::{
+    api::{
+        owner::{
+            select_owner_by_email, update_owner_audited, validate_password, ApiPayload, OwnersIden,
+        },
+        session::revoke_owner_sessions,
+    },
+    audit::{Actor, RequestContext},
+    db,
+    mailer::Email,
+    password, redact, token, AppState,
+};
+use axum::{response::IntoResponse, routing::post, Extension, Json, Router};
+use chrono::{Duration, Utc};
+use hyper::StatusCode;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, PgPool, Row};
+use std::{fmt, sync::Arc};
+
+#[derive(Iden)]
+pub enum PasswordResetTokens {
+    Table,
+    OwnerId,
+    TokenHash,
+    ExpiresAt,
+    ConsumedAt,
+    CreatedAt,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RequestPasswordReset {
+    pub email: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ConfirmPasswordReset {
+    pub token: String,
+    pub new_password: String,
+}
+
+impl fmt::Debug for ConfirmPasswordReset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "ConfirmPasswordReset", f)
+    }
+}
+
+/// Mails a password reset link to `email` if it belongs to an owner who verified it. Unknown and
+/// unverified addresses are silently ignored so callers can't tell them apart. An unverified
+/// address may have been set by someone else, e.g. support staff, and must not grant a login.
+#[tracing::instrument(name = "SEND a password reset token", skip(email, state))]
+pub async fn send_password_reset_email(email: &str, state: &AppState) -> anyhow::Result<()> {
+    let owner = match select_owner_by_email(email, &state.db).await {
+        Ok(owner) => owner,
+        Err(sqlx::Error::RowNotFound) => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+
+    if owner.email_verified_at.is_none() {
+        return Ok(());
+    }
+
+    let token = token::generate();
+
+    let expires_at = Utc::now() + Duration::minutes(state.config.password_reset_ttl_minutes);
+
+    let (sql, values) = Query::insert()
+        .into_table(PasswordResetTokens::Table)
+        .columns([
+            PasswordResetTokens::OwnerId,
+            PasswordResetTokens::TokenHash,
+            PasswordResetTokens::ExpiresAt,
+        ])
+        .values_panic([
+            owner.id.into(),
+            token::hash(&token).into(),
+            expires_at.into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&state.db).await?;
+
+    state
+        .mailer
+        .send(Email {
+            to: owner.email,
+            subject: String::from("Reset your password"),
+            body: format!(
+                "Choose a new password by visiting {}/password-reset?token={}\n\n\
+                 The link expires at {}. If you didn't ask for a reset you can ignore this email.",
+                state.config.public_base_url, token, expires_at
+            ),
+        })
+        .await
+}
+
+/// Consumes a reset token, stores the new password hash and revokes every session of the owner.
+/// The change is audited and published like any other owner update.
+///
+/// Returns the owner id, or `None` when the token is unknown, expired or already used.
+#[tracing::instrument(name = "RESET an owner's password", skip_all)]
+pub async fn reset_password(
+    token: &str,
+    password_hash: String,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<i32>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::update()
+        .table(PasswordResetTokens::Table)
+        .value(PasswordResetTokens::ConsumedAt, Expr::current_timestamp())
+        .and_where(Expr::col(PasswordResetTokens::TokenHash).eq(token::hash(token)))
+        .and_where(Expr::col(PasswordResetTokens::ConsumedAt).is_null())
+        .and_where(Expr::col(PasswordResetTokens::ExpiresAt).gt(Expr::current_timestamp()))
+        .returning(Query::returning().columns([PasswordResetTokens::OwnerId]))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let Some(owner_id) = db::query_with(&sql, values)
+        .map(|row: PgRow| row.get::<i32, _>("owner_id"))
+        .fetch_optional(&mut tx)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    if update_owner_audited(
+        owner_id,
+        Query::update()
+            .table(OwnersIden::Table)
+            .value(OwnersIden::Password, password_hash)
+            .and_where(Expr::col(OwnersIden::DeletedAt).is_null()),
+        "owner_password_reset",
+        actor,
+        &mut tx,
+    )
+    .await?
+    .is_none()
+    {
+        return Ok(None);
+    }
+
+    // Any other outstanding reset link is now stale.
+    let (sql, values) = Query::update()
+        .table(PasswordResetTokens::Table)
+        .value(PasswordResetTokens::ConsumedAt, Expr::current_timestamp())
+        .and_where(Expr::col(PasswordResetTokens::OwnerId).eq(owner_id))
+        .and_where(Expr::col(PasswordResetTokens::ConsumedAt).is_null())
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    revoke_owner_sessions(owner_id, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(Some(owner_id))
+}
+
+#[tracing::instrument(name = "Request a password reset", skip_all)]
+pub async fn post_password_reset(
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<RequestPasswordReset>>,
+) -> impl IntoResponse {
+    // Respond before doing any work so timing doesn't reveal whether the email exists.
+    tokio::spawn(async move {
+        if let Err(error) = send_password_reset_email(&req.payload.email, &state).await {
+            tracing::error!("Failed to send password reset email: {:?}", error);
+        }
+    });
+
+    StatusCode::ACCEPTED
+}
+
+#[tracing::instrument(name = "Confirm a password reset", skip_all)]
+pub async fn post_password_reset_confirm(
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<ConfirmPasswordReset>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    validate_password(&req.payload.new_password)?;
+
+    let password_hash = match password::hash(req.payload.new_password).await {
+        Ok(hash) => hash,
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
+    match reset_password(
+        &req.payload.token,
+        password_hash,
+        &Actor::anonymous(&context),
+        &state.db,
+    )
+    .await
+    {
+        Ok(Some(_)) => Ok(StatusCode::NO_CONTENT),
+        Ok(None) => Err((
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid or expired password reset token"),
+        )),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/owner/password-reset", post(post_password_reset))
+        .route(
+            "/owner/password-reset/confirm",
+            post(post_password_reset_confirm),
+        )
+}
@@ -0,0 +1,328 @@
+use crate::{
+    api::{
+        api_key::{select_api_keys, ApiKey, ApiKeys},
+        owner::{owner_from_row, update_owner_audited, Owners, OwnersIden, OWNER_COLUMNS},
+        password_reset::PasswordResetTokens,
+        session::Sessions,
+        verification::EmailVerificationTokens,
+        webhook::{select_webhooks, Webhook, WebhookSubscriptions},
+    },
+    audit::{self, Actor, AuditEvent, AuditRecord, RequestContext},
+    auth::AuthenticatedOwner,
+    db, password,
+    rbac::{select_owner_roles, OwnerRoles, Permission, Role, Scope},
+    token, AppState,
+};
+use axum::{extract::Path, response::IntoResponse, routing::get, Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use http::header::CONTENT_DISPOSITION;
+use hyper::StatusCode;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, PgPool, Row};
+use std::sync::Arc;
+
+#[derive(Iden)]
+pub enum OwnerErasures {
+    Table,
+    OwnerId,
+    RequestedBy,
+    Credential,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SessionExport {
+    pub id: i32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EmailVerificationExport {
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PasswordResetExport {
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+/// Everything stored about an owner. Secrets (password, token and key hashes, webhook signing
+/// secrets) are left out.
+#[derive(Deserialize, Serialize)]
+pub struct OwnerExport {
+    pub exported_at: DateTime<Utc>,
+    pub owner: Owners,
+    pub roles: Vec<Role>,
+    pub sessions: Vec<SessionExport>,
+    pub api_keys: Vec<ApiKey>,
+    pub email_verifications: Vec<EmailVerificationExport>,
+    pub password_resets: Vec<PasswordResetExport>,
+    pub webhooks: Vec<Webhook>,
+    pub audit_events: Vec<AuditRecord>,
+}
+
+/// Collects an owner's data, soft-deleted owners included, and records the export in the audit
+/// log. Returns `None` for unknown ids.
+#[tracing::instrument(name = "EXPORT an owner's data", skip(db))]
+pub async fn export_owner(
+    id: i32,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<OwnerExport>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(OWNER_COLUMNS)
+        .from(OwnersIden::Table)
+        .and_where(Expr::col(OwnersIden::Id).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let Some(owner) = db::query_with(&sql, values)
+        .map(owner_from_row)
+        .fetch_optional(db)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let (sql, values) = Query::select()
+        .columns([
+            Sessions::Id,
+            Sessions::CreatedAt,
+            Sessions::ExpiresAt,
+            Sessions::RevokedAt,
+        ])
+        .from(Sessions::Table)
+        .and_where(Expr::col(Sessions::OwnerId).eq(id))
+        .order_by(Sessions::Id, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let sessions = db::query_with(&sql, values)
+        .map(|row: PgRow| SessionExport {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            revoked_at: row.get("revoked_at"),
+        })
+        .fetch_all(db)
+        .await?;
+
+    let (sql, values) = Query::select()
+        .columns([
+            EmailVerificationTokens::Email,
+            EmailVerificationTokens::CreatedAt,
+            EmailVerificationTokens::ExpiresAt,
+            EmailVerificationTokens::ConsumedAt,
+        ])
+        .from(EmailVerificationTokens::Table)
+        .and_where(Expr::col(EmailVerificationTokens::OwnerId).eq(id))
+        .order_by(EmailVerificationTokens::CreatedAt, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let email_verifications = db::query_with(&sql, values)
+        .map(|row: PgRow| EmailVerificationExport {
+            email: row.get("email"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            consumed_at: row.get("consumed_at"),
+        })
+        .fetch_all(db)
+        .await?;
+
+    let (sql, values) = Query::select()
+        .columns([
+            PasswordResetTokens::CreatedAt,
+            PasswordResetTokens::ExpiresAt,
+            PasswordResetTokens::ConsumedAt,
+        ])
+        .from(PasswordResetTokens::Table)
+        .and_where(Expr::col(PasswordResetTokens::OwnerId).eq(id))
+        .order_by(PasswordResetTokens::CreatedAt, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let password_resets = db::query_with(&sql, values)
+        .map(|row: PgRow| PasswordResetExport {
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            consumed_at: row.get("consumed_at"),
+        })
+        .fetch_all(db)
+        .await?;
+
+    let export = OwnerExport {
+        exported_at: Utc::now(),
+        owner,
+        roles: select_owner_roles(id, db).await?,
+        sessions,
+        api_keys: select_api_keys(id, db).await?,
+        email_verifications,
+        password_resets,
+        webhooks: select_webhooks(id, db).await?,
+        audit_events: audit::select_target_events("owner", id, db).await?,
+    };
+
+    audit::record(
+        actor,
+        AuditEvent {
+            action: "owner_exported",
+            target_type: "owner",
+            target_id: Some(id),
+            diff: serde_json::json!({}),
+        },
+        db,
+    )
+    .await?;
+
+    Ok(Some(export))
+}
+
+/// Irreversibly anonymizes an owner and removes its dependent data, recording who asked.
+///
+/// The `owners` row and revoked API keys are kept, scrubbed, so references to them stay valid.
+/// The owner's audit events keep which fields changed but lose their values.
+/// Returns `false` when the owner doesn't exist or was already erased.
+#[tracing::instrument(name = "ERASE an owner", skip(db))]
+pub async fn erase_owner(id: i32, actor: &Actor, db: &PgPool) -> Result<bool, sqlx::Error> {
+    // Nobody knows this password, so the account can't be logged into even if restored by hand.
+    let unusable_password = password::hash(token::generate())
+        .await
+        .map_err(|error| sqlx::Error::Protocol(error.to_string()))?;
+
+    let mut tx = db.begin().await?;
+
+    let erased = update_owner_audited(
+        id,
+        Query::update()
+            .table(OwnersIden::Table)
+            .value(OwnersIden::Name, "Erased owner")
+            .value(OwnersIden::Email, format!("erased-{}@invalid", id))
+            .value(OwnersIden::Password, unusable_password)
+            .value(OwnersIden::EmailVerifiedAt, Option::<DateTime<Utc>>::None)
+            .value(
+                OwnersIden::DeletedAt,
+                Func::coalesce([
+                    Expr::col(OwnersIden::DeletedAt).into(),
+                    Expr::current_timestamp().into(),
+                ]),
+            )
+            .value(OwnersIden::ErasedAt, Expr::current_timestamp())
+            .and_where(Expr::col(OwnersIden::ErasedAt).is_null()),
+        "owner_erased",
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    if erased.is_none() {
+        return Ok(false);
+    }
+
+    // Deleting subscriptions takes their deliveries and attempt log along.
+    let dependents: [(DynIden, DynIden); 5] = [
+        (SeaRc::new(Sessions::Table), SeaRc::new(Sessions::OwnerId)),
+        (
+            SeaRc::new(EmailVerificationTokens::Table),
+            SeaRc::new(EmailVerificationTokens::OwnerId),
+        ),
+        (
+            SeaRc::new(PasswordResetTokens::Table),
+            SeaRc::new(PasswordResetTokens::OwnerId),
+        ),
+        (
+            SeaRc::new(OwnerRoles::Table),
+            SeaRc::new(OwnerRoles::OwnerId),
+        ),
+        (
+            SeaRc::new(WebhookSubscriptions::Table),
+            SeaRc::new(WebhookSubscriptions::OwnerId),
+        ),
+    ];
+
+    for (table, owner_id) in dependents {
+        let (sql, values) = Query::delete()
+            .from_table(table)
+            .and_where(Expr::col(owner_id).eq(id))
+            .build_sqlx(PostgresQueryBuilder);
+
+        db::query_with(&sql, values).execute(&mut tx).await?;
+    }
+
+    let (sql, values) = Query::update()
+        .table(ApiKeys::Table)
+        .value(ApiKeys::Name, "")
+        .value(ApiKeys::AllowedIps, Vec::<ipnetwork::IpNetwork>::new())
+        .value(
+            ApiKeys::RevokedAt,
+            Func::coalesce([
+                Expr::col(ApiKeys::RevokedAt).into(),
+                Expr::current_timestamp().into(),
+            ]),
+        )
+        .and_where(Expr::col(ApiKeys::OwnerId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    let (sql, values) = Query::insert()
+        .into_table(OwnerErasures::Table)
+        .columns([
+            OwnerErasures::OwnerId,
+            OwnerErasures::RequestedBy,
+            OwnerErasures::Credential,
+        ])
+        .values_panic([
+            id.into(),
+            actor.owner_id.into(),
+            actor.credential.clone().into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    // Last, so the erasure's own event loses the values it replaced too.
+    audit::scrub("owner", id, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+#[tracing::instrument(name = "Export an Owner's data", skip(state))]
+pub async fn get_owner_export(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::OwnerRead)?;
+
+    owner.ensure_owner_or(id, Permission::ReadOwners)?;
+
+    match export_owner(id, &Actor::owner(&owner, &context), &state.db).await {
+        Ok(Some(export)) => Ok((
+            [(
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"owner-{}-export.json\"", id),
+            )],
+            Json(export),
+        )),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            format!("Record not found for id: {:?}", id),
+        )),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+pub fn router() -> Router {
+    Router::new().route("/owner/:id/export", get(get_owner_export))
+}
@@ -0,0 +1,341 @@
+use crate::{
+    api::owner::{select_owner_by_email, ApiPayload, OwnersIden},
+    audit::{self, Actor, AuditEvent, RequestContext},
+    auth::{AuthenticatedOwner, ClientIp},
+    db,
+    lockout::ThrottleKey,
+    password, redact, token, AppState,
+};
+use axum::{
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use http::header::RETRY_AFTER;
+use hyper::StatusCode;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Executor, PgPool, Postgres, Row};
+use std::{fmt, sync::Arc};
+
+#[derive(Iden)]
+pub enum Sessions {
+    Table,
+    Id,
+    OwnerId,
+    TokenHash,
+    ExpiresAt,
+    RevokedAt,
+    CreatedAt,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateSession {
+    pub email: String,
+    pub password: String,
+}
+
+impl fmt::Debug for CreateSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "CreateSession", f)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateSessionResponse {
+    pub token: String,
+    pub owner_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for CreateSessionResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "CreateSessionResponse", f)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Session {
+    pub id: i32,
+    pub owner_id: i32,
+}
+
+#[tracing::instrument(name = "CREATE a session", skip(db))]
+pub async fn create_session(
+    owner_id: i32,
+    ttl_minutes: i64,
+    db: &PgPool,
+) -> Result<CreateSessionResponse, sqlx::Error> {
+    let token = token::generate();
+
+    let expires_at = Utc::now() + Duration::minutes(ttl_minutes);
+
+    let (sql, values) = Query::insert()
+        .into_table(Sessions::Table)
+        .columns([Sessions::OwnerId, Sessions::TokenHash, Sessions::ExpiresAt])
+        .values_panic([
+            owner_id.into(),
+            token::hash(&token).into(),
+            expires_at.into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(db).await?;
+
+    Ok(CreateSessionResponse {
+        token,
+        owner_id,
+        expires_at,
+    })
+}
+
+/// Sessions that are usable: not revoked, not expired, and belonging to an owner that still
+/// exists.
+fn live_sessions() -> SelectStatement {
+    Query::select()
+        .column((Sessions::Table, Sessions::Id))
+        .column((Sessions::Table, Sessions::OwnerId))
+        .from(Sessions::Table)
+        .inner_join(
+            OwnersIden::Table,
+            Expr::col((OwnersIden::Table, OwnersIden::Id))
+                .equals((Sessions::Table, Sessions::OwnerId)),
+        )
+        .and_where(Expr::col((Sessions::Table, Sessions::RevokedAt)).is_null())
+        .and_where(Expr::col((Sessions::Table, Sessions::ExpiresAt)).gt(Expr::current_timestamp()))
+        .and_where(Expr::col((OwnersIden::Table, OwnersIden::DeletedAt)).is_null())
+        .to_owned()
+}
+
+/// Looks up a live session by its token.
+#[tracing::instrument(name = "SELECT a session by token", skip(token, db))]
+pub async fn find_session(token: &str, db: &PgPool) -> Result<Option<Session>, sqlx::Error> {
+    let (sql, values) = live_sessions()
+        .and_where(Expr::col((Sessions::Table, Sessions::TokenHash)).eq(token::hash(token)))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(|row: PgRow| Session {
+            id: row.get("id"),
+            owner_id: row.get("owner_id"),
+        })
+        .fetch_optional(db)
+        .await
+}
+
+/// Whether session `id` is still live, for requests that outlast a single check.
+#[tracing::instrument(name = "SELECT whether a session is live", skip(db))]
+pub async fn session_is_live(id: i32, db: &PgPool) -> Result<bool, sqlx::Error> {
+    let (sql, values) = live_sessions()
+        .and_where(Expr::col((Sessions::Table, Sessions::Id)).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .fetch_optional(db)
+        .await
+        .map(|row| row.is_some())
+}
+
+#[tracing::instrument(name = "REVOKE a session", skip(db))]
+pub async fn revoke_session(id: i32, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let (sql, values) = Query::update()
+        .table(Sessions::Table)
+        .value(Sessions::RevokedAt, Expr::current_timestamp())
+        .and_where(Expr::col(Sessions::Id).eq(id))
+        .and_where(Expr::col(Sessions::RevokedAt).is_null())
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+/// Revokes every live session of an owner. Accepts a transaction so callers can revoke
+/// atomically with the change that invalidates them.
+#[tracing::instrument(name = "REVOKE all sessions of an owner", skip(executor))]
+pub async fn revoke_owner_sessions<'c, E>(owner_id: i32, executor: E) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::update()
+        .table(Sessions::Table)
+        .value(Sessions::RevokedAt, Expr::current_timestamp())
+        .and_where(Expr::col(Sessions::OwnerId).eq(owner_id))
+        .and_where(Expr::col(Sessions::RevokedAt).is_null())
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+/// Revokes every live session of an owner except `keep_session_id`.
+#[tracing::instrument(name = "REVOKE other sessions of an owner", skip(executor))]
+pub async fn revoke_other_sessions<'c, E>(
+    owner_id: i32,
+    keep_session_id: i32,
+    executor: E,
+) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::update()
+        .table(Sessions::Table)
+        .value(Sessions::RevokedAt, Expr::current_timestamp())
+        .and_where(Expr::col(Sessions::OwnerId).eq(owner_id))
+        .and_where(Expr::col(Sessions::Id).ne(keep_session_id))
+        .and_where(Expr::col(Sessions::RevokedAt).is_null())
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+fn too_many_attempts(retry_after: std::time::Duration) -> Response {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, seconds.to_string())],
+        String::from("Too many failed login attempts, try again later"),
+    )
+        .into_response()
+}
+
+/// Counts a failed login against every key and audits the ones it locks. Account lockouts are
+/// recorded against the owner when the email belongs to one, client IP lockouts without a
+/// target row.
+async fn failed_login(
+    state: &AppState,
+    keys: &[ThrottleKey],
+    owner_id: Option<i32>,
+    actor: &Actor,
+) -> Response {
+    for key in state.login_throttle.record_failure(keys) {
+        let event = match key {
+            ThrottleKey::Account(_) => match owner_id {
+                Some(owner_id) => AuditEvent {
+                    action: "login_locked",
+                    target_type: "owner",
+                    target_id: Some(owner_id),
+                    diff: serde_json::json!({}),
+                },
+                // Nobody to protect, and the address isn't worth keeping.
+                None => continue,
+            },
+            ThrottleKey::Ip(ip) => AuditEvent {
+                action: "login_locked",
+                target_type: "client_ip",
+                target_id: None,
+                diff: audit::diff(None, Some(&serde_json::json!({ "ip": ip }))),
+            },
+        };
+
+        if let Err(error) = audit::record(actor, event, &state.db).await {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )
+                .into_response();
+        }
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        String::from("Invalid email or password"),
+    )
+        .into_response()
+}
+
+#[tracing::instrument(name = "Log in an Owner", skip(state, req))]
+pub async fn post_session(
+    ClientIp(ip): ClientIp,
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<CreateSession>>,
+) -> Result<Response, Response> {
+    let keys = [
+        ThrottleKey::account(&req.payload.email),
+        ThrottleKey::Ip(ip),
+    ];
+
+    if let Err(retry_after) = state.login_throttle.check(&keys) {
+        return Err(too_many_attempts(retry_after));
+    }
+
+    let actor = Actor::anonymous(&context);
+
+    let owner = match select_owner_by_email(&req.payload.email, &state.db).await {
+        Ok(owner) => Some(owner),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )
+                .into_response())
+        }
+    };
+
+    let verified = match &owner {
+        Some(owner) => password::verify(req.payload.password, owner.password.clone()).await,
+        None => password::verify_unknown(req.payload.password).await,
+    };
+
+    let owner = match (verified, owner) {
+        (Ok(true), Some(owner)) => owner,
+        (Ok(_), owner) => {
+            return Err(failed_login(&state, &keys, owner.map(|owner| owner.id), &actor).await)
+        }
+        (Err(error), _) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )
+                .into_response())
+        }
+    };
+
+    state.login_throttle.reset(&keys[0]);
+
+    match create_session(owner.id, state.config.session_ttl_minutes, &state.db).await {
+        Ok(session) => Ok((StatusCode::CREATED, Json(session)).into_response()),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )
+            .into_response()),
+    }
+}
+
+#[tracing::instrument(name = "Log out an Owner", skip(state))]
+pub async fn delete_session(
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let session_id = owner.require_session()?;
+
+    match revoke_session(session_id, &state.db).await {
+        Ok(_) => {
+            tracing::info!(owner_id = owner.owner_id, "owner logged out");
+
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/session", post(post_session))
+        .route("/session", delete(delete_session))
+}
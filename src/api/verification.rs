@@ -0,0 +1,203 @@
+use crate::{
+    api::owner::{select_owner, update_owner_audited, OwnersIden},
+    audit::{Actor, RequestContext},
+    auth::AuthenticatedOwner,
+    db,
+    mailer::Email,
+    rbac::{Permission, Scope},
+    token, AppState,
+};
+use axum::{
+    extract::{Path, Query as QueryParams},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Router,
+};
+use chrono::{Duration, Utc};
+use hyper::StatusCode;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, PgPool, Row};
+use std::sync::Arc;
+
+#[derive(Iden)]
+pub enum EmailVerificationTokens {
+    Table,
+    OwnerId,
+    Email,
+    TokenHash,
+    ExpiresAt,
+    ConsumedAt,
+    CreatedAt,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+/// Issues a fresh verification token for `email` and mails the verification link to it.
+#[tracing::instrument(name = "SEND an email verification token", skip(state))]
+pub async fn send_verification_email(
+    owner_id: i32,
+    email: &str,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    let token = token::generate();
+
+    let expires_at = Utc::now() + Duration::minutes(state.config.email_verification_ttl_minutes);
+
+    let (sql, values) = Query::insert()
+        .into_table(EmailVerificationTokens::Table)
+        .columns([
+            EmailVerificationTokens::OwnerId,
+            EmailVerificationTokens::Email,
+            EmailVerificationTokens::TokenHash,
+            EmailVerificationTokens::ExpiresAt,
+        ])
+        .values_panic([
+            owner_id.into(),
+            email.into(),
+            token::hash(&token).into(),
+            expires_at.into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&state.db).await?;
+
+    state
+        .mailer
+        .send(Email {
+            to: email.to_string(),
+            subject: String::from("Verify your email address"),
+            body: format!(
+                "Confirm your email address by visiting {}/verify?token={}\n\n\
+                 The link expires at {}.",
+                state.config.public_base_url, token, expires_at
+            ),
+        })
+        .await
+}
+
+/// Consumes a verification token and marks the owner's email as verified.
+///
+/// Returns the owner id, or `None` when the token is unknown, expired, already used, or was
+/// issued for an address the owner no longer uses.
+#[tracing::instrument(name = "CONSUME an email verification token", skip(token, db))]
+pub async fn consume_verification_token(
+    token: &str,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<i32>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::update()
+        .table(EmailVerificationTokens::Table)
+        .value(
+            EmailVerificationTokens::ConsumedAt,
+            Expr::current_timestamp(),
+        )
+        .and_where(Expr::col(EmailVerificationTokens::TokenHash).eq(token::hash(token)))
+        .and_where(Expr::col(EmailVerificationTokens::ConsumedAt).is_null())
+        .and_where(Expr::col(EmailVerificationTokens::ExpiresAt).gt(Expr::current_timestamp()))
+        .returning(Query::returning().columns([
+            EmailVerificationTokens::OwnerId,
+            EmailVerificationTokens::Email,
+        ]))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let Some((owner_id, email)) = db::query_with(&sql, values)
+        .map(|row: PgRow| (row.get::<i32, _>("owner_id"), row.get::<String, _>("email")))
+        .fetch_optional(&mut tx)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let verified = update_owner_audited(
+        owner_id,
+        Query::update()
+            .table(OwnersIden::Table)
+            .value(OwnersIden::EmailVerifiedAt, Expr::current_timestamp())
+            .and_where(Expr::col(OwnersIden::Email).eq(email))
+            .and_where(Expr::col(OwnersIden::DeletedAt).is_null()),
+        "owner_email_verified",
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(verified.map(|_| owner_id))
+}
+
+#[tracing::instrument(name = "Request an email verification link", skip(state))]
+pub async fn post_verify_email(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::OwnerWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    let record = match select_owner(id, &state.db).await {
+        Ok(record) => record,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Record not found for id: {:?}", id),
+            ))
+        }
+        Err(error) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            ))
+        }
+    };
+
+    if record.email_verified_at.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            String::from("Email address is already verified"),
+        ));
+    }
+
+    match send_verification_email(record.id, &record.email, &state).await {
+        Ok(()) => Ok(StatusCode::ACCEPTED),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Verify an email address", skip(state, params))]
+pub async fn get_verify(
+    context: RequestContext,
+    QueryParams(params): QueryParams<VerifyEmailQuery>,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    match consume_verification_token(&params.token, &Actor::anonymous(&context), &state.db).await {
+        Ok(Some(_)) => Ok((StatusCode::OK, "Email address verified")),
+        Ok(None) => Err((
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid or expired verification token"),
+        )),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/owner/:id/verify-email", post(post_verify_email))
+        .route("/verify", get(get_verify))
+}
@@ -0,0 +1,1352 @@
+use crate::{
+    api::owner::ApiPayload,
+    audit::{self, Actor, AuditEvent, RequestContext},
+    auth::AuthenticatedOwner,
+    db,
+    outbox::{self, DomainEvent, Outbox},
+    rbac::{Permission, Scope},
+    redact, telemetry, token, AppState, Settings,
+};
+use axum::{
+    extract::{Path, Query as QueryParams},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use hyper::StatusCode;
+use sea_query::{extension::postgres::PgFunc, *};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::{postgres::PgRow, Executor, PgPool, Postgres, Row, Transaction};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use url::{Host, Url};
+
+type Rejection = (StatusCode, String);
+
+#[derive(Iden)]
+pub enum WebhookSubscriptions {
+    Table,
+    Id,
+    OwnerId,
+    Url,
+    EventTypes,
+    Secret,
+    Active,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+pub enum WebhookDeliveries {
+    Table,
+    Id,
+    SubscriptionId,
+    EventId,
+    Status,
+    Attempts,
+    NextAttemptAt,
+    ResponseStatus,
+    LastError,
+    DeliveredAt,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+pub enum WebhookDeliveryAttempts {
+    Table,
+    DeliveryId,
+    ResponseStatus,
+    Error,
+    DurationMs,
+    AttemptedAt,
+}
+
+/// Event types a subscription may ask for. Owners only ever receive events about themselves.
+pub const EVENT_TYPES: [&str; 3] = ["owner.created", "owner.updated", "owner.deleted"];
+
+const MIN_SECRET_LENGTH: usize = 16;
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+
+const MAX_PAGE_SIZE: u64 = 500;
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateWebhook {
+    pub url: String,
+    pub event_types: Vec<String>,
+    /// Key for the delivery signatures. One is generated when omitted.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl fmt::Debug for CreateWebhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "CreateWebhook", f)
+    }
+}
+
+/// Returned once on creation. `secret` can't be retrieved again, only replaced.
+#[derive(Deserialize, Serialize)]
+pub struct CreateWebhookResponse {
+    pub id: i32,
+    pub secret: String,
+}
+
+impl fmt::Debug for CreateWebhookResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "CreateWebhookResponse", f)
+    }
+}
+
+/// Partial update of a subscription; absent fields are left alone.
+#[derive(Deserialize, Serialize, Default)]
+pub struct UpdateWebhook {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub event_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub active: Option<bool>,
+}
+
+impl fmt::Debug for UpdateWebhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::debug(self, "UpdateWebhook", f)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Webhook {
+    pub id: i32,
+    pub owner_id: i32,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+/// One event sent to one subscription, with every attempt made so far, oldest first.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub event_id: i64,
+    pub event_type: String,
+    pub status: DeliveryStatus,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub attempts: Vec<DeliveryAttempt>,
+}
+
+/// A single POST to the receiver. `response_status` is missing when no response came back.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DeliveryAttempt {
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ListDeliveriesQuery {
+    pub limit: Option<u64>,
+}
+
+const WEBHOOK_COLUMNS: [WebhookSubscriptions; 7] = [
+    WebhookSubscriptions::Id,
+    WebhookSubscriptions::OwnerId,
+    WebhookSubscriptions::Url,
+    WebhookSubscriptions::EventTypes,
+    WebhookSubscriptions::Active,
+    WebhookSubscriptions::CreatedAt,
+    WebhookSubscriptions::UpdatedAt,
+];
+
+fn webhook_from_row(row: PgRow) -> Webhook {
+    Webhook {
+        id: row.get("id"),
+        owner_id: row.get("owner_id"),
+        url: row.get("url"),
+        event_types: row.get("event_types"),
+        active: row.get("active"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// What the audit log keeps of a subscription. The secret only shows up as changed.
+fn audited(webhook: &Webhook, secret: &str) -> Value {
+    serde_json::json!({
+        "url": webhook.url,
+        "event_types": webhook.event_types,
+        "active": webhook.active,
+        "secret": secret,
+    })
+}
+
+/// Records a change to subscription `id`. Events are about its owner, so they're exported and
+/// erased along with the owner's; the subscription is named in the diff.
+async fn record_webhook_change(
+    id: i32,
+    owner_id: i32,
+    action: &'static str,
+    before: Option<&Value>,
+    after: Option<&Value>,
+    actor: &Actor,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    let mut diff = audit::diff(before, after);
+
+    diff["webhook_id"] = serde_json::json!({
+        "before": before.map(|_| id),
+        "after": after.map(|_| id),
+    });
+
+    audit::record(
+        actor,
+        AuditEvent {
+            action,
+            target_type: "owner",
+            target_id: Some(owner_id),
+            diff,
+        },
+        &mut *tx,
+    )
+    .await
+}
+
+/// A subscription with its secret, locked for the rest of the transaction.
+async fn lock_webhook(
+    owner_id: i32,
+    id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<(Webhook, String)>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(WEBHOOK_COLUMNS)
+        .column(WebhookSubscriptions::Secret)
+        .from(WebhookSubscriptions::Table)
+        .and_where(Expr::col(WebhookSubscriptions::Id).eq(id))
+        .and_where(Expr::col(WebhookSubscriptions::OwnerId).eq(owner_id))
+        .lock(LockType::Update)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(|row: PgRow| {
+            let secret: String = row.get("secret");
+
+            (webhook_from_row(row), secret)
+        })
+        .fetch_optional(&mut *tx)
+        .await
+}
+
+fn generate_secret() -> String {
+    format!("whsec_{}", token::generate())
+}
+
+/// Whether `ip` is reachable on the public internet. Loopback, private, link-local (cloud
+/// metadata endpoints among them) and reserved addresses are off limits, or owners could make
+/// the dispatcher probe the internal network and read the answers in the delivery log.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // "This network", carrier-grade NAT, benchmarking and reserved for future use.
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let [first, second, ..] = ip.segments();
+
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // Unique local, link-local and documentation.
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+                    || (first == 0x2001 && second == 0x0db8))
+            }
+        },
+    }
+}
+
+/// Addresses the host of `url` resolves to. Fails when it resolves to none, or to any that
+/// isn't public unless `allow_private` is set.
+async fn resolve(url: &Url, allow_private: bool) -> Result<Vec<SocketAddr>, String> {
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<SocketAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|error| format!("Failed to resolve {}: {}", domain, error))?
+            .collect(),
+        None => return Err(String::from("URL has no host")),
+    };
+
+    if addrs.is_empty() {
+        return Err(format!("{} doesn't resolve to any address", url));
+    }
+
+    match addrs
+        .iter()
+        .find(|addr| !allow_private && !is_public(addr.ip()))
+    {
+        Some(addr) => Err(format!(
+            "{} resolves to non-public address {}",
+            url,
+            addr.ip()
+        )),
+        None => Ok(addrs),
+    }
+}
+
+async fn validate_url(url: &str, allow_private: bool) -> Result<(), Rejection> {
+    let parsed = match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => parsed,
+        _ => {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Webhook URL must be an absolute http(s) URL: {:?}", url),
+            ))
+        }
+    };
+
+    resolve(&parsed, allow_private)
+        .await
+        .map(|_| ())
+        .map_err(|error| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Webhook URL must point to a public address: {}", error),
+            )
+        })
+}
+
+fn validate_event_types(event_types: &[String]) -> Result<(), Rejection> {
+    if event_types.is_empty() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("Webhook must subscribe to at least one event type"),
+        ));
+    }
+
+    match event_types
+        .iter()
+        .find(|event_type| !EVENT_TYPES.contains(&event_type.as_str()))
+    {
+        Some(unknown) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Unknown event type: {}", unknown),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn validate_secret(secret: &str) -> Result<(), Rejection> {
+    if secret.chars().count() < MIN_SECRET_LENGTH {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "Webhook secret must be at least {} characters",
+                MIN_SECRET_LENGTH
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the subscription secret. Receivers
+/// recompute it from the `x-webhook-timestamp` header and the raw body, and should reject
+/// stale timestamps to stop replays.
+fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+
+    mac.update(timestamp.to_string().as_bytes());
+
+    mac.update(b".");
+
+    mac.update(body);
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[tracing::instrument(name = "CREATE a webhook subscription", skip(webhook, secret, db))]
+pub async fn create_webhook(
+    owner_id: i32,
+    webhook: &CreateWebhook,
+    secret: &str,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<i32, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::insert()
+        .into_table(WebhookSubscriptions::Table)
+        .columns([
+            WebhookSubscriptions::OwnerId,
+            WebhookSubscriptions::Url,
+            WebhookSubscriptions::EventTypes,
+            WebhookSubscriptions::Secret,
+        ])
+        .values_panic([
+            owner_id.into(),
+            webhook.url.clone().into(),
+            webhook.event_types.clone().into(),
+            secret.into(),
+        ])
+        .returning(Query::returning().columns(WEBHOOK_COLUMNS))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let created = db::query_with(&sql, values)
+        .map(webhook_from_row)
+        .fetch_one(&mut tx)
+        .await?;
+
+    record_webhook_change(
+        created.id,
+        owner_id,
+        "webhook_created",
+        None,
+        Some(&audited(&created, secret)),
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(created.id)
+}
+
+#[tracing::instrument(name = "SELECT an owner's webhook subscriptions", skip(db))]
+pub async fn select_webhooks(owner_id: i32, db: &PgPool) -> Result<Vec<Webhook>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(WEBHOOK_COLUMNS)
+        .from(WebhookSubscriptions::Table)
+        .and_where(Expr::col(WebhookSubscriptions::OwnerId).eq(owner_id))
+        .order_by(WebhookSubscriptions::Id, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(webhook_from_row)
+        .fetch_all(db)
+        .await
+}
+
+#[tracing::instrument(name = "SELECT a webhook subscription", skip(db))]
+pub async fn select_webhook(
+    owner_id: i32,
+    id: i32,
+    db: &PgPool,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(WEBHOOK_COLUMNS)
+        .from(WebhookSubscriptions::Table)
+        .and_where(Expr::col(WebhookSubscriptions::Id).eq(id))
+        .and_where(Expr::col(WebhookSubscriptions::OwnerId).eq(owner_id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(webhook_from_row)
+        .fetch_optional(db)
+        .await
+}
+
+/// Updates a subscription. Deactivating it fails its pending deliveries, since they'd never be
+/// sent; they can still be redelivered once it's active again.
+#[tracing::instrument(name = "UPDATE a webhook subscription", skip(webhook, db))]
+pub async fn update_webhook(
+    owner_id: i32,
+    id: i32,
+    webhook: UpdateWebhook,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let Some((before, before_secret)) = lock_webhook(owner_id, id, &mut tx).await? else {
+        return Ok(None);
+    };
+
+    let deactivated = webhook.active == Some(false);
+
+    let mut update = Query::update();
+
+    update
+        .table(WebhookSubscriptions::Table)
+        .value(WebhookSubscriptions::UpdatedAt, Expr::current_timestamp())
+        .and_where(Expr::col(WebhookSubscriptions::Id).eq(id))
+        .and_where(Expr::col(WebhookSubscriptions::OwnerId).eq(owner_id))
+        .returning(
+            Query::returning().columns(
+                WEBHOOK_COLUMNS
+                    .into_iter()
+                    .chain([WebhookSubscriptions::Secret]),
+            ),
+        );
+
+    if let Some(url) = webhook.url {
+        update.value(WebhookSubscriptions::Url, url);
+    }
+
+    if let Some(event_types) = webhook.event_types {
+        update.value(WebhookSubscriptions::EventTypes, event_types);
+    }
+
+    if let Some(secret) = webhook.secret {
+        update.value(WebhookSubscriptions::Secret, secret);
+    }
+
+    if let Some(active) = webhook.active {
+        update.value(WebhookSubscriptions::Active, active);
+    }
+
+    let (sql, values) = update.build_sqlx(PostgresQueryBuilder);
+
+    let (updated, secret) = db::query_with(&sql, values)
+        .map(|row: PgRow| {
+            let secret: String = row.get("secret");
+
+            (webhook_from_row(row), secret)
+        })
+        .fetch_one(&mut tx)
+        .await?;
+
+    record_webhook_change(
+        id,
+        owner_id,
+        "webhook_updated",
+        Some(&audited(&before, &before_secret)),
+        Some(&audited(&updated, &secret)),
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    if deactivated {
+        let (sql, values) = Query::update()
+            .table(WebhookDeliveries::Table)
+            .value(WebhookDeliveries::Status, DeliveryStatus::Failed.as_str())
+            .value(WebhookDeliveries::LastError, "Subscription was deactivated")
+            .and_where(Expr::col(WebhookDeliveries::SubscriptionId).eq(id))
+            .and_where(Expr::col(WebhookDeliveries::Status).eq(DeliveryStatus::Pending.as_str()))
+            .build_sqlx(PostgresQueryBuilder);
+
+        db::query_with(&sql, values).execute(&mut tx).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(updated))
+}
+
+#[tracing::instrument(name = "DELETE a webhook subscription", skip(db))]
+pub async fn delete_webhook(
+    owner_id: i32,
+    id: i32,
+    actor: &Actor,
+    db: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let Some((before, secret)) = lock_webhook(owner_id, id, &mut tx).await? else {
+        return Ok(0);
+    };
+
+    let (sql, values) = Query::delete()
+        .from_table(WebhookSubscriptions::Table)
+        .and_where(Expr::col(WebhookSubscriptions::Id).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let deleted = db::query_with(&sql, values)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+    record_webhook_change(
+        id,
+        owner_id,
+        "webhook_deleted",
+        Some(&audited(&before, &secret)),
+        None,
+        actor,
+        &mut tx,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(deleted)
+}
+
+/// Queues a delivery of outbox event `event_id` to every active subscription of `owner_id`
+/// asking for `event_type`. Pass the transaction that enqueued the event.
+#[tracing::instrument(name = "INSERT webhook deliveries", skip(executor))]
+pub async fn fan_out<'c, E>(
+    event_id: i64,
+    owner_id: i32,
+    event_type: &str,
+    executor: E,
+) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::insert()
+        .into_table(WebhookDeliveries::Table)
+        .columns([
+            WebhookDeliveries::SubscriptionId,
+            WebhookDeliveries::EventId,
+        ])
+        .select_from(
+            Query::select()
+                .column(WebhookSubscriptions::Id)
+                .expr(Expr::val(event_id))
+                .from(WebhookSubscriptions::Table)
+                .and_where(Expr::col(WebhookSubscriptions::OwnerId).eq(owner_id))
+                .and_where(Expr::col(WebhookSubscriptions::Active).eq(true))
+                .and_where(
+                    Expr::val(event_type)
+                        .eq(PgFunc::any(Expr::col(WebhookSubscriptions::EventTypes))),
+                )
+                .to_owned(),
+        )
+        .map_err(|error| sqlx::Error::Protocol(error.to_string()))?
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+/// The most recent deliveries of a subscription, newest first.
+#[tracing::instrument(name = "SELECT webhook deliveries", skip(db))]
+pub async fn select_deliveries(
+    subscription_id: i32,
+    limit: u64,
+    db: &PgPool,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(
+            [
+                WebhookDeliveries::Id,
+                WebhookDeliveries::EventId,
+                WebhookDeliveries::Status,
+                WebhookDeliveries::ResponseStatus,
+                WebhookDeliveries::LastError,
+                WebhookDeliveries::NextAttemptAt,
+                WebhookDeliveries::DeliveredAt,
+                WebhookDeliveries::CreatedAt,
+            ]
+            .map(|column| (WebhookDeliveries::Table, column)),
+        )
+        .column((Outbox::Table, Outbox::EventType))
+        .from(WebhookDeliveries::Table)
+        .inner_join(
+            Outbox::Table,
+            Expr::col((Outbox::Table, Outbox::Id))
+                .equals((WebhookDeliveries::Table, WebhookDeliveries::EventId)),
+        )
+        .and_where(
+            Expr::col((WebhookDeliveries::Table, WebhookDeliveries::SubscriptionId))
+                .eq(subscription_id),
+        )
+        .order_by(
+            (WebhookDeliveries::Table, WebhookDeliveries::Id),
+            Order::Desc,
+        )
+        .limit(limit)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let mut deliveries = db::query_with(&sql, values)
+        .map(|row: PgRow| WebhookDelivery {
+            id: row.get("id"),
+            event_id: row.get("event_id"),
+            event_type: row.get("event_type"),
+            status: match row.get::<String, _>("status").as_str() {
+                "delivered" => DeliveryStatus::Delivered,
+                "failed" => DeliveryStatus::Failed,
+                _ => DeliveryStatus::Pending,
+            },
+            response_status: row.get("response_status"),
+            last_error: row.get("last_error"),
+            next_attempt_at: row.get("next_attempt_at"),
+            delivered_at: row.get("delivered_at"),
+            created_at: row.get("created_at"),
+            attempts: Vec::new(),
+        })
+        .fetch_all(db)
+        .await?;
+
+    if deliveries.is_empty() {
+        return Ok(deliveries);
+    }
+
+    let (sql, values) = Query::select()
+        .columns([
+            WebhookDeliveryAttempts::DeliveryId,
+            WebhookDeliveryAttempts::ResponseStatus,
+            WebhookDeliveryAttempts::Error,
+            WebhookDeliveryAttempts::DurationMs,
+            WebhookDeliveryAttempts::AttemptedAt,
+        ])
+        .from(WebhookDeliveryAttempts::Table)
+        .and_where(
+            Expr::col(WebhookDeliveryAttempts::DeliveryId)
+                .is_in(deliveries.iter().map(|delivery| delivery.id)),
+        )
+        .order_by(WebhookDeliveryAttempts::AttemptedAt, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let attempts = db::query_with(&sql, values)
+        .map(|row: PgRow| {
+            let delivery_id: i64 = row.get("delivery_id");
+
+            let attempt = DeliveryAttempt {
+                response_status: row.get("response_status"),
+                error: row.get("error"),
+                duration_ms: row.get("duration_ms"),
+                attempted_at: row.get("attempted_at"),
+            };
+
+            (delivery_id, attempt)
+        })
+        .fetch_all(db)
+        .await?;
+
+    let mut by_delivery: HashMap<i64, Vec<DeliveryAttempt>> = HashMap::new();
+
+    for (delivery_id, attempt) in attempts {
+        by_delivery.entry(delivery_id).or_default().push(attempt);
+    }
+
+    for delivery in &mut deliveries {
+        delivery.attempts = by_delivery.remove(&delivery.id).unwrap_or_default();
+    }
+
+    Ok(deliveries)
+}
+
+/// Queues a delivery again with a fresh retry budget, whatever its current status.
+#[tracing::instrument(name = "UPDATE a webhook delivery for redelivery", skip(db))]
+pub async fn redeliver(
+    subscription_id: i32,
+    delivery_id: i64,
+    db: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let (sql, values) = Query::update()
+        .table(WebhookDeliveries::Table)
+        .value(WebhookDeliveries::Status, DeliveryStatus::Pending.as_str())
+        .value(WebhookDeliveries::Attempts, 0)
+        .value(WebhookDeliveries::NextAttemptAt, Expr::current_timestamp())
+        .and_where(Expr::col(WebhookDeliveries::Id).eq(delivery_id))
+        .and_where(Expr::col(WebhookDeliveries::SubscriptionId).eq(subscription_id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+/// Tunables for [`dispatch`], read from `Settings`.
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryPolicy {
+    pub batch_size: u64,
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    pub max_attempts: i32,
+    pub timeout: Duration,
+    pub allow_private_targets: bool,
+}
+
+impl DeliveryPolicy {
+    pub fn from_settings(config: &Settings) -> Self {
+        Self {
+            batch_size: config.webhook_batch_size,
+            retry_base: Duration::from_millis(config.webhook_retry_base_ms),
+            retry_max: Duration::from_millis(config.webhook_retry_max_ms),
+            max_attempts: config.webhook_max_attempts,
+            timeout: Duration::from_millis(config.webhook_timeout_ms),
+            allow_private_targets: config.webhook_allow_private_targets,
+        }
+    }
+}
+
+struct PendingDelivery {
+    id: i64,
+    attempts: i32,
+    url: String,
+    secret: String,
+    event: DomainEvent,
+}
+
+/// Result of one POST: the receiver's status code if it answered, and why it counts as a
+/// failure if it does.
+struct Outcome {
+    response_status: Option<i32>,
+    error: Option<String>,
+}
+
+/// A client that only connects to the addresses the delivery's host resolves to right now, so a
+/// host can't pass the check and then point the request elsewhere. Redirects aren't followed
+/// for the same reason.
+async fn client_for(url: &str, policy: &DeliveryPolicy) -> Result<reqwest::Client, String> {
+    let parsed = Url::parse(url).map_err(|error| format!("Invalid URL: {}", error))?;
+
+    let addrs = resolve(&parsed, policy.allow_private_targets).await?;
+
+    let mut builder = reqwest::Client::builder()
+        .timeout(policy.timeout)
+        .redirect(reqwest::redirect::Policy::none());
+
+    if let Some(Host::Domain(domain)) = parsed.host() {
+        builder = builder.resolve_to_addrs(domain, &addrs);
+    }
+
+    builder.build().map_err(|error| error.to_string())
+}
+
+/// POSTs the event as JSON. Besides the signature, receivers get the event type and ids in
+/// headers so they can route and dedupe without parsing the body.
+#[tracing::instrument(
+    name = "POST a webhook delivery",
+    skip(policy, delivery),
+    fields(delivery_id = delivery.id, event_id = delivery.event.id)
+)]
+async fn send(policy: &DeliveryPolicy, delivery: &PendingDelivery) -> Outcome {
+    let client = match client_for(&delivery.url, policy).await {
+        Ok(client) => client,
+        Err(error) => {
+            return Outcome {
+                response_status: None,
+                error: Some(error),
+            }
+        }
+    };
+
+    let body = match serde_json::to_vec(&delivery.event) {
+        Ok(body) => body,
+        Err(error) => {
+            return Outcome {
+                response_status: None,
+                error: Some(format!("Failed to serialize event: {}", error)),
+            }
+        }
+    };
+
+    let timestamp = Utc::now().timestamp();
+
+    let signature = sign(&delivery.secret, timestamp, &body);
+
+    let response = telemetry::inject_context(client.post(&delivery.url))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("x-webhook-id", delivery.id)
+        .header("x-webhook-event", &delivery.event.event_type)
+        .header("x-webhook-timestamp", timestamp)
+        .header("x-webhook-signature", format!("sha256={}", signature))
+        .header("x-event-id", delivery.event.id)
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => Outcome {
+            response_status: Some(response.status().as_u16().into()),
+            error: None,
+        },
+        Ok(response) => Outcome {
+            response_status: Some(response.status().as_u16().into()),
+            error: Some(format!("Receiver answered {}", response.status())),
+        },
+        Err(error) => Outcome {
+            response_status: None,
+            error: Some(error.to_string()),
+        },
+    }
+}
+
+/// Attempts one batch of due deliveries, oldest first, and returns how many were attempted.
+///
+/// The batch is claimed in a short transaction by pushing its next attempt past the time the
+/// sends may take, then sent without holding a connection. Every attempt is logged. Failures
+/// are retried with exponential backoff until `max_attempts`, after which the delivery is
+/// marked failed and only a manual redelivery sends it again. Like the outbox, deliveries are
+/// at least once: a crash mid-batch sends the rest once the claim lapses.
+#[tracing::instrument(name = "DISPATCH webhook deliveries", skip(db))]
+pub async fn dispatch(policy: DeliveryPolicy, db: &PgPool) -> Result<usize, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::select()
+        .expr_as(
+            Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Id)),
+            Alias::new("delivery_id"),
+        )
+        .column((WebhookDeliveries::Table, WebhookDeliveries::Attempts))
+        .columns(
+            [WebhookSubscriptions::Url, WebhookSubscriptions::Secret]
+                .map(|column| (WebhookSubscriptions::Table, column)),
+        )
+        .columns(
+            [
+                Outbox::Id,
+                Outbox::AggregateType,
+                Outbox::AggregateId,
+                Outbox::EventType,
+                Outbox::Payload,
+                Outbox::CreatedAt,
+            ]
+            .map(|column| (Outbox::Table, column)),
+        )
+        .from(WebhookDeliveries::Table)
+        .inner_join(
+            WebhookSubscriptions::Table,
+            Expr::col((WebhookSubscriptions::Table, WebhookSubscriptions::Id))
+                .equals((WebhookDeliveries::Table, WebhookDeliveries::SubscriptionId)),
+        )
+        .inner_join(
+            Outbox::Table,
+            Expr::col((Outbox::Table, Outbox::Id))
+                .equals((WebhookDeliveries::Table, WebhookDeliveries::EventId)),
+        )
+        .and_where(
+            Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Status))
+                .eq(DeliveryStatus::Pending.as_str()),
+        )
+        .and_where(
+            Expr::col((WebhookDeliveries::Table, WebhookDeliveries::NextAttemptAt))
+                .lte(Expr::current_timestamp()),
+        )
+        .and_where(Expr::col((WebhookSubscriptions::Table, WebhookSubscriptions::Active)).eq(true))
+        .order_by(
+            (WebhookDeliveries::Table, WebhookDeliveries::Id),
+            Order::Asc,
+        )
+        .limit(policy.batch_size)
+        .lock_with_tables_behavior(
+            LockType::Update,
+            [WebhookDeliveries::Table],
+            LockBehavior::SkipLocked,
+        )
+        .build_sqlx(PostgresQueryBuilder);
+
+    let deliveries = db::query_with(&sql, values)
+        .map(|row: PgRow| PendingDelivery {
+            id: row.get("delivery_id"),
+            attempts: row.get("attempts"),
+            url: row.get("url"),
+            secret: row.get("secret"),
+            event: DomainEvent {
+                id: row.get("id"),
+                aggregate_type: row.get("aggregate_type"),
+                aggregate_id: row.get("aggregate_id"),
+                event_type: row.get("event_type"),
+                payload: row.get("payload"),
+                created_at: row.get("created_at"),
+            },
+        })
+        .fetch_all(&mut tx)
+        .await?;
+
+    let attempted = deliveries.len();
+
+    if attempted == 0 {
+        return Ok(0);
+    }
+
+    let claimed_until = Utc::now()
+        + chrono::Duration::from_std(policy.timeout.saturating_mul(attempted as u32 + 1))
+            .unwrap_or_else(|_| chrono::Duration::max_value());
+
+    let (sql, values) = Query::update()
+        .table(WebhookDeliveries::Table)
+        .value(WebhookDeliveries::NextAttemptAt, claimed_until)
+        .and_where(
+            Expr::col(WebhookDeliveries::Id).is_in(deliveries.iter().map(|delivery| delivery.id)),
+        )
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    tx.commit().await?;
+
+    for delivery in deliveries {
+        let started = Instant::now();
+
+        let outcome = send(&policy, &delivery).await;
+
+        let duration_ms: i32 = TryFrom::try_from(started.elapsed().as_millis()).unwrap_or(i32::MAX);
+
+        record_attempt(&delivery, outcome, duration_ms, policy, db).await?;
+    }
+
+    Ok(attempted)
+}
+
+/// Logs an attempt and moves the delivery on: delivered, retried later or failed for good.
+async fn record_attempt(
+    delivery: &PendingDelivery,
+    outcome: Outcome,
+    duration_ms: i32,
+    policy: DeliveryPolicy,
+    db: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::insert()
+        .into_table(WebhookDeliveryAttempts::Table)
+        .columns([
+            WebhookDeliveryAttempts::DeliveryId,
+            WebhookDeliveryAttempts::ResponseStatus,
+            WebhookDeliveryAttempts::Error,
+            WebhookDeliveryAttempts::DurationMs,
+        ])
+        .values_panic([
+            delivery.id.into(),
+            outcome.response_status.into(),
+            outcome.error.clone().into(),
+            duration_ms.into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    let mut update = Query::update();
+
+    update
+        .table(WebhookDeliveries::Table)
+        .value(WebhookDeliveries::ResponseStatus, outcome.response_status)
+        .value(WebhookDeliveries::LastError, outcome.error.clone())
+        .and_where(Expr::col(WebhookDeliveries::Id).eq(delivery.id));
+
+    match outcome.error {
+        None => {
+            update
+                .value(
+                    WebhookDeliveries::Status,
+                    DeliveryStatus::Delivered.as_str(),
+                )
+                .value(WebhookDeliveries::DeliveredAt, Expr::current_timestamp());
+        }
+        Some(error) => {
+            let attempts = delivery.attempts + 1;
+
+            tracing::warn!(
+                delivery_id = delivery.id,
+                event_id = delivery.event.id,
+                attempts,
+                "Failed to deliver webhook: {}",
+                error
+            );
+
+            update.value(WebhookDeliveries::Attempts, attempts);
+
+            if attempts >= policy.max_attempts {
+                update.value(WebhookDeliveries::Status, DeliveryStatus::Failed.as_str());
+            } else {
+                let retry_at = Utc::now()
+                    + chrono::Duration::from_std(outbox::backoff(
+                        policy.retry_base,
+                        policy.retry_max,
+                        attempts,
+                    ))
+                    .unwrap_or_else(|_| chrono::Duration::zero());
+
+                update.value(WebhookDeliveries::NextAttemptAt, retry_at);
+            }
+        }
+    }
+
+    let (sql, values) = update.build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    tx.commit().await
+}
+
+#[tracing::instrument(name = "Create a webhook subscription", skip(state, req))]
+pub async fn post_webhook(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<CreateWebhook>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    validate_url(&req.payload.url, state.config.webhook_allow_private_targets).await?;
+
+    validate_event_types(&req.payload.event_types)?;
+
+    let secret = match &req.payload.secret {
+        Some(secret) => {
+            validate_secret(secret)?;
+
+            secret.clone()
+        }
+        None => generate_secret(),
+    };
+
+    let actor = Actor::owner(&owner, &context);
+
+    match create_webhook(id, &req.payload, &secret, &actor, &state.db).await {
+        Ok(webhook_id) => Ok((
+            StatusCode::CREATED,
+            Json(CreateWebhookResponse {
+                id: webhook_id,
+                secret,
+            }),
+        )),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "List an owner's webhook subscriptions", skip(state))]
+pub async fn get_webhooks(
+    Path(id): Path<i32>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookRead)?;
+
+    owner.ensure_owner_or(id, Permission::ReadOwners)?;
+
+    match select_webhooks(id, &state.db).await {
+        Ok(webhooks) => Ok(Json(webhooks)),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Get a webhook subscription", skip(state))]
+pub async fn get_webhook(
+    Path((id, webhook_id)): Path<(i32, i32)>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookRead)?;
+
+    owner.ensure_owner_or(id, Permission::ReadOwners)?;
+
+    match select_webhook(id, webhook_id, &state.db).await {
+        Ok(Some(webhook)) => Ok(Json(webhook)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No webhook subscription found for id: {:?}", webhook_id),
+        )),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Update a webhook subscription", skip(state, req))]
+pub async fn patch_webhook(
+    Path((id, webhook_id)): Path<(i32, i32)>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+    Json(req): Json<ApiPayload<UpdateWebhook>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    if let Some(url) = &req.payload.url {
+        validate_url(url, state.config.webhook_allow_private_targets).await?;
+    }
+
+    if let Some(event_types) = &req.payload.event_types {
+        validate_event_types(event_types)?;
+    }
+
+    if let Some(secret) = &req.payload.secret {
+        validate_secret(secret)?;
+    }
+
+    let actor = Actor::owner(&owner, &context);
+
+    match update_webhook(id, webhook_id, req.payload, &actor, &state.db).await {
+        Ok(Some(webhook)) => Ok(Json(webhook)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No webhook subscription found for id: {:?}", webhook_id),
+        )),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Delete a webhook subscription", skip(state))]
+pub async fn delete_webhook_subscription(
+    Path((id, webhook_id)): Path<(i32, i32)>,
+    owner: AuthenticatedOwner,
+    context: RequestContext,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    let actor = Actor::owner(&owner, &context);
+
+    match delete_webhook(id, webhook_id, &actor, &state.db).await {
+        Ok(0) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No webhook subscription found for id: {:?}", webhook_id),
+        )),
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "List webhook deliveries", skip(state))]
+pub async fn get_webhook_deliveries(
+    Path((id, webhook_id)): Path<(i32, i32)>,
+    QueryParams(params): QueryParams<ListDeliveriesQuery>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookRead)?;
+
+    owner.ensure_owner_or(id, Permission::ReadOwners)?;
+
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    let deliveries = match select_webhook(id, webhook_id, &state.db).await {
+        Ok(Some(_)) => select_deliveries(webhook_id, limit, &state.db).await,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("No webhook subscription found for id: {:?}", webhook_id),
+            ))
+        }
+        Err(error) => Err(error),
+    };
+
+    match deliveries {
+        Ok(deliveries) => Ok(Json(deliveries)),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+#[tracing::instrument(name = "Redeliver a webhook delivery", skip(state))]
+pub async fn post_redeliver(
+    Path((id, webhook_id, delivery_id)): Path<(i32, i32, i64)>,
+    owner: AuthenticatedOwner,
+    state: Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    owner.require_scope(Scope::WebhookWrite)?;
+
+    owner
+        .ensure_may_modify(id, Permission::UpdateOwners, &state.db)
+        .await?;
+
+    let redelivered = match select_webhook(id, webhook_id, &state.db).await {
+        Ok(Some(_)) => redeliver(webhook_id, delivery_id, &state.db).await,
+        Ok(None) => Ok(0),
+        Err(error) => Err(error),
+    };
+
+    match redelivered {
+        Ok(0) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No webhook delivery found for id: {:?}", delivery_id),
+        )),
+        Ok(_) => Ok(StatusCode::ACCEPTED),
+        Err(error) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unknown Error: {:?}", error),
+        )),
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/owner/:id/webhooks", get(get_webhooks).post(post_webhook))
+        .route(
+            "/owner/:id/webhooks/:webhook_id",
+            get(get_webhook)
+                .patch(patch_webhook)
+                .delete(delete_webhook_subscription),
+        )
+        .route(
+            "/owner/:id/webhooks/:webhook_id/deliveries",
+            get(get_webhook_deliveries),
+        )
+        .route(
+            "/owner/:id/webhooks/:webhook_id/deliveries/:delivery_id/redeliver",
+            post(post_redeliver),
+        )
+}
@@ -0,0 +1,301 @@
+use crate::{auth::AuthenticatedOwner, db};
+use axum::{async_trait, extract::FromRequestParts};
+use chrono::{DateTime, Utc};
+use http::request::Parts;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{postgres::PgRow, Executor, PgPool, Postgres, Row, Transaction};
+use std::convert::Infallible;
+use tower_http::request_id::RequestId;
+
+/// Fields whose values never reach the audit log, only the fact that they changed.
+const REDACTED_FIELDS: [&str; 4] = ["password", "token_hash", "key_hash", "secret"];
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Iden)]
+pub enum AuditEvents {
+    Table,
+    Id,
+    OccurredAt,
+    ActorOwnerId,
+    ActorCredential,
+    Action,
+    TargetType,
+    TargetId,
+    RequestId,
+    Diff,
+}
+
+const AUDIT_EVENT_COLUMNS: [AuditEvents; 9] = [
+    AuditEvents::Id,
+    AuditEvents::OccurredAt,
+    AuditEvents::ActorOwnerId,
+    AuditEvents::ActorCredential,
+    AuditEvents::Action,
+    AuditEvents::TargetType,
+    AuditEvents::TargetId,
+    AuditEvents::RequestId,
+    AuditEvents::Diff,
+];
+
+/// Request metadata recorded alongside mutations.
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    pub request_id: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(String::from);
+
+        Ok(RequestContext { request_id })
+    }
+}
+
+/// Who performed a mutation. Both ids are empty for anonymous requests and background jobs.
+#[derive(Debug, Clone, Default)]
+pub struct Actor {
+    pub owner_id: Option<i32>,
+    pub credential: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl Actor {
+    pub fn owner(owner: &AuthenticatedOwner, context: &RequestContext) -> Self {
+        Self {
+            owner_id: Some(owner.owner_id),
+            credential: Some(owner.credential.to_string()),
+            request_id: context.request_id.clone(),
+        }
+    }
+
+    pub fn anonymous(context: &RequestContext) -> Self {
+        Self {
+            request_id: context.request_id.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Mutations made by the service itself, e.g. scheduled jobs or test fixtures.
+    pub fn system() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug)]
+pub struct AuditEvent {
+    pub action: &'static str,
+    pub target_type: &'static str,
+    /// Empty when the target isn't a row, e.g. a client IP or the process' log level.
+    pub target_id: Option<i32>,
+    pub diff: Value,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor_owner_id: Option<i32>,
+    pub actor_credential: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<i32>,
+    pub request_id: Option<String>,
+    pub diff: Value,
+}
+
+/// Field-level diff of two JSON objects as `{ field: { before, after } }`, listing only fields
+/// that changed. `None` stands for a record that didn't exist before or doesn't exist after.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+
+    let before = before.and_then(Value::as_object).unwrap_or(&empty);
+
+    let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut changes = Map::new();
+
+    for field in before.keys().chain(after.keys()) {
+        let (old, new) = (before.get(field), after.get(field));
+
+        if old == new || changes.contains_key(field) {
+            continue;
+        }
+
+        let redact = |value: Option<&Value>| match value {
+            Some(_) if REDACTED_FIELDS.contains(&field.as_str()) => Value::from(REDACTED),
+            Some(value) => value.clone(),
+            None => Value::Null,
+        };
+
+        let mut change = Map::new();
+
+        change.insert(String::from("before"), redact(old));
+
+        change.insert(String::from("after"), redact(new));
+
+        changes.insert(field.clone(), Value::Object(change));
+    }
+
+    Value::Object(changes)
+}
+
+/// Appends an event. Pass the mutation's transaction so both commit or roll back together.
+#[tracing::instrument(name = "INSERT an audit event", skip(executor))]
+pub async fn record<'c, E>(actor: &Actor, event: AuditEvent, executor: E) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::insert()
+        .into_table(AuditEvents::Table)
+        .columns([
+            AuditEvents::ActorOwnerId,
+            AuditEvents::ActorCredential,
+            AuditEvents::Action,
+            AuditEvents::TargetType,
+            AuditEvents::TargetId,
+            AuditEvents::RequestId,
+            AuditEvents::Diff,
+        ])
+        .values_panic([
+            actor.owner_id.into(),
+            actor.credential.clone().into(),
+            event.action.into(),
+            event.target_type.into(),
+            event.target_id.into(),
+            actor.request_id.clone().into(),
+            event.diff.into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(executor)
+        .await
+        .map(|_| ())
+}
+
+/// Replaces every value in the diffs of a target's events, keeping which fields changed.
+/// Must run inside the erasure's transaction.
+#[tracing::instrument(name = "SCRUB audit events", skip(tx))]
+pub async fn scrub(
+    target_type: &'static str,
+    target_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<u64, sqlx::Error> {
+    db::query("SET LOCAL audit.scrub = 'on'")
+        .execute(&mut *tx)
+        .await?;
+
+    let scrubbed = Expr::cust(
+        "COALESCE((SELECT jsonb_object_agg(key, \
+         '{\"before\": \"[ERASED]\", \"after\": \"[ERASED]\"}'::jsonb) \
+         FROM jsonb_each(diff)), '{}'::jsonb)",
+    );
+
+    let (sql, values) = Query::update()
+        .table(AuditEvents::Table)
+        .value(AuditEvents::Diff, scrubbed)
+        .and_where(Expr::col(AuditEvents::TargetType).eq(target_type))
+        .and_where(Expr::col(AuditEvents::TargetId).eq(target_id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(&mut *tx)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AuditQuery {
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<u64>,
+}
+
+/// Events matching `query`, newest first. `from` is inclusive, `to` exclusive.
+#[tracing::instrument(name = "SELECT audit events", skip(db))]
+pub async fn select_events(
+    query: &AuditQuery,
+    limit: u64,
+    db: &PgPool,
+) -> Result<Vec<AuditRecord>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(AUDIT_EVENT_COLUMNS)
+        .from(AuditEvents::Table)
+        .and_where_option(
+            query
+                .target_type
+                .as_ref()
+                .map(|target_type| Expr::col(AuditEvents::TargetType).eq(target_type.as_str())),
+        )
+        .and_where_option(
+            query
+                .target_id
+                .map(|target_id| Expr::col(AuditEvents::TargetId).eq(target_id)),
+        )
+        .and_where_option(
+            query
+                .from
+                .map(|from| Expr::col(AuditEvents::OccurredAt).gte(from)),
+        )
+        .and_where_option(query.to.map(|to| Expr::col(AuditEvents::OccurredAt).lt(to)))
+        .order_by(AuditEvents::OccurredAt, Order::Desc)
+        .order_by(AuditEvents::Id, Order::Desc)
+        .limit(limit)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(record_from_row)
+        .fetch_all(db)
+        .await
+}
+
+/// Every event about one target, oldest first.
+#[tracing::instrument(name = "SELECT a target's audit events", skip(db))]
+pub async fn select_target_events(
+    target_type: &'static str,
+    target_id: i32,
+    db: &PgPool,
+) -> Result<Vec<AuditRecord>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(AUDIT_EVENT_COLUMNS)
+        .from(AuditEvents::Table)
+        .and_where(Expr::col(AuditEvents::TargetType).eq(target_type))
+        .and_where(Expr::col(AuditEvents::TargetId).eq(target_id))
+        .order_by(AuditEvents::Id, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(record_from_row)
+        .fetch_all(db)
+        .await
+}
+
+fn record_from_row(row: PgRow) -> AuditRecord {
+    AuditRecord {
+        id: row.get("id"),
+        occurred_at: row.get("occurred_at"),
+        actor_owner_id: row.get("actor_owner_id"),
+        actor_credential: row.get("actor_credential"),
+        action: row.get("action"),
+        target_type: row.get("target_type"),
+        target_id: row.get("target_id"),
+        request_id: row.get("request_id"),
+        diff: row.get("diff"),
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    api::{
+        api_key::{api_key_is_usable, find_api_key},
+        session::{find_session, session_is_live},
+    },
+    rbac::{self, Permission, Role, Scope},
+    AppState,
+};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, State},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use http::{header::AUTHORIZATION, request::Parts, Request};
+use hyper::StatusCode;
+use sqlx::PgPool;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+type Rejection = (StatusCode, String);
+
+async fn app_state<S>(parts: &mut Parts, state: &S) -> Result<Arc<AppState>, Rejection>
+where
+    S: Send + Sync,
+{
+    Extension::<Arc<AppState>>::from_request_parts(parts, state)
+        .await
+        .map(|Extension(app)| app)
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))
+}
+
+fn authorization<'a>(parts: &'a Parts, scheme: &str) -> Option<&'a str> {
+    parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(scheme))
+        .and_then(|value| value.strip_prefix(' '))
+}
+
+/// How the caller authenticated.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Session { id: i32 },
+    ApiKey { id: i32, scopes: Vec<Scope> },
+}
+
+impl fmt::Display for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credential::Session { id } => write!(f, "session:{}", id),
+            Credential::ApiKey { id, .. } => write!(f, "api_key:{}", id),
+        }
+    }
+}
+
+/// Extractor for routes that require an authenticated owner.
+///
+/// Requests must carry either `Authorization: Bearer <session token>` as issued by
+/// `POST /session`, or `Authorization: ApiKey <key>` for machine clients. Sessions carry the
+/// owner's roles; API keys are limited to their scopes and never carry staff roles.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedOwner {
+    pub owner_id: i32,
+    pub roles: Vec<Role>,
+    pub credential: Credential,
+}
+
+impl AuthenticatedOwner {
+    /// Returns the session id, rejecting API keys on routes meant for interactive use only.
+    pub fn require_session(&self) -> Result<i32, Rejection> {
+        match self.credential {
+            Credential::Session { id } => Ok(id),
+            Credential::ApiKey { .. } => Err((
+                StatusCode::FORBIDDEN,
+                String::from("This action requires a session, not an API key"),
+            )),
+        }
+    }
+
+    /// Rejects API keys lacking `scope`. Sessions act with the owner's full rights.
+    pub fn require_scope(&self, scope: Scope) -> Result<(), Rejection> {
+        match &self.credential {
+            Credential::Session { .. } => Ok(()),
+            Credential::ApiKey { scopes, .. } if scopes.contains(&scope) => Ok(()),
+            Credential::ApiKey { .. } => Err((
+                StatusCode::FORBIDDEN,
+                format!("API key is missing the {} scope", scope),
+            )),
+        }
+    }
+
+    /// Whether the credential would still be accepted, for long-lived requests such as event
+    /// streams: sessions end on logout, expiry or a password reset, API keys on revocation or
+    /// expiry, and both when the owner is deleted.
+    pub async fn is_still_valid(&self, db: &PgPool) -> Result<bool, sqlx::Error> {
+        match self.credential {
+            Credential::Session { id } => session_is_live(id, db).await,
+            Credential::ApiKey { id, .. } => api_key_is_usable(id, db).await,
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        rbac::has_permission(&self.roles, permission)
+    }
+
+    /// Rejects the request unless one of the owner's roles grants `permission`.
+    pub fn require(&self, permission: Permission) -> Result<(), Rejection> {
+        if self.has_permission(permission) {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                format!("Missing permission: {:?}", permission),
+            ))
+        }
+    }
+
+    /// Rejects the request unless the session belongs to owner `id`.
+    pub fn ensure_owner(&self, id: i32) -> Result<(), Rejection> {
+        if self.owner_id == id {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                String::from("Not allowed to modify another owner"),
+            ))
+        }
+    }
+
+    /// Like [`ensure_owner`](Self::ensure_owner), but staff holding `permission` may act on
+    /// any owner.
+    pub fn ensure_owner_or(&self, id: i32, permission: Permission) -> Result<(), Rejection> {
+        if self.owner_id == id || self.has_permission(permission) {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                String::from("Not allowed to access another owner"),
+            ))
+        }
+    }
+
+    /// Like [`ensure_owner_or`](Self::ensure_owner_or) for changes: staff may only change owners
+    /// that don't outrank them, so support can't take over an admin's account.
+    pub async fn ensure_may_modify(
+        &self,
+        id: i32,
+        permission: Permission,
+        db: &PgPool,
+    ) -> Result<(), Rejection> {
+        self.ensure_owner_or(id, permission)?;
+
+        if self.owner_id == id {
+            return Ok(());
+        }
+
+        match rbac::select_owner_roles(id, db).await {
+            Ok(roles) if rbac::outranks(&roles, &self.roles) => Err((
+                StatusCode::FORBIDDEN,
+                String::from("Not allowed to modify an owner with a higher role"),
+            )),
+            Ok(_) => Ok(()),
+            Err(error) => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedOwner
+where
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Already resolved by `require_permission` when the route is guarded by it.
+        if let Some(owner) = parts.extensions.get::<AuthenticatedOwner>() {
+            return Ok(owner.clone());
+        }
+
+        let app = app_state(parts, state).await?;
+
+        if let Some(key) = authorization(parts, "ApiKey") {
+            return match find_api_key(key, &app.db).await {
+                Ok(Some(api_key)) => {
+                    let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+
+                    if !api_key.allows_ip(ip) {
+                        return Err((
+                            StatusCode::FORBIDDEN,
+                            String::from("API key is not allowed from this address"),
+                        ));
+                    }
+
+                    Ok(AuthenticatedOwner {
+                        owner_id: api_key.owner_id,
+                        roles: Vec::new(),
+                        credential: Credential::ApiKey {
+                            id: api_key.id,
+                            scopes: api_key.scopes,
+                        },
+                    })
+                }
+                Ok(None) => Err((
+                    StatusCode::UNAUTHORIZED,
+                    String::from("Invalid, expired or revoked API key"),
+                )),
+                Err(error) => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Unknown Error: {:?}", error),
+                )),
+            };
+        }
+
+        let token = authorization(parts, "Bearer").ok_or((
+            StatusCode::UNAUTHORIZED,
+            String::from("Missing session token"),
+        ))?;
+
+        let session = match find_session(token, &app.db).await {
+            Ok(Some(session)) => session,
+            Ok(None) => {
+                return Err((
+                    StatusCode::UNAUTHORIZED,
+                    String::from("Invalid or expired session"),
+                ))
+            }
+            Err(error) => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Unknown Error: {:?}", error),
+                ))
+            }
+        };
+
+        match rbac::select_owner_roles(session.owner_id, &app.db).await {
+            Ok(roles) => Ok(AuthenticatedOwner {
+                owner_id: session.owner_id,
+                roles,
+                credential: Credential::Session { id: session.id },
+            }),
+            Err(error) => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown Error: {:?}", error),
+            )),
+        }
+    }
+}
+
+/// Middleware guarding a whole router with a single permission, e.g.
+///
+/// ```ignore
+/// router.route_layer(middleware::from_fn_with_state(Permission::ReadOwners, require_permission))
+/// ```
+///
+/// The resolved [`AuthenticatedOwner`] is stored in the request extensions so handlers
+/// extracting it again don't repeat the lookup.
+pub async fn require_permission<B>(
+    State(permission): State<Permission>,
+    owner: AuthenticatedOwner,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Result<Response, Rejection> {
+    owner.require(permission)?;
+
+    req.extensions_mut().insert(owner);
+
+    Ok(next.run(req).await)
+}
+
+/// Address of the client that sent the request.
+///
+/// Uses the first address of the `client_ip_header` setting (e.g. `x-forwarded-for`) when
+/// configured, which must only be done behind a proxy that overwrites that header.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let app = app_state(parts, state).await?;
+
+        let forwarded = app
+            .config
+            .client_ip_header
+            .as_deref()
+            .and_then(|header| parts.headers.get(header))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|value| value.trim().parse::<IpAddr>().ok());
+
+        if let Some(ip) = forwarded {
+            return Ok(ClientIp(ip));
+        }
+
+        ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map(|ConnectInfo(addr)| ClientIp(addr.ip()))
+            .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))
+    }
+}
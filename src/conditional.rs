@@ -0,0 +1,104 @@
+use axum::{async_trait, extract::FromRequestParts};
+use http::{
+    header::{HeaderName, IF_MATCH, IF_NONE_MATCH},
+    request::Parts,
+    HeaderMap,
+};
+use hyper::StatusCode;
+use std::convert::Infallible;
+
+type Rejection = (StatusCode, String);
+
+/// Strong entity tag for a resource version, as sent in `ETag`.
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Any,
+    Tags(Vec<String>),
+}
+
+fn condition(headers: &HeaderMap, name: HeaderName) -> Option<Condition> {
+    let mut tags = Vec::new();
+
+    for value in headers.get_all(name) {
+        for tag in value.to_str().unwrap_or_default().split(',') {
+            match tag.trim() {
+                "" => {}
+                "*" => return Some(Condition::Any),
+                tag => tags.push(tag.to_string()),
+            }
+        }
+    }
+
+    (!tags.is_empty()).then_some(Condition::Tags(tags))
+}
+
+fn tag_version(tag: &str) -> Option<i32> {
+    tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
+
+/// Extractor for the `If-Match` header of updates guarded against lost writes.
+#[derive(Debug, Clone)]
+pub struct IfMatch(Option<Condition>);
+
+impl IfMatch {
+    /// Versions the update may apply to, or `None` for `If-Match: *`. Tags are compared
+    /// strongly, so weak tags match nothing. Fails with `428` when the header is missing.
+    pub fn require(&self) -> Result<Option<Vec<i32>>, Rejection> {
+        match &self.0 {
+            None => Err((
+                StatusCode::PRECONDITION_REQUIRED,
+                String::from("This request requires an If-Match header"),
+            )),
+            Some(Condition::Any) => Ok(None),
+            Some(Condition::Tags(tags)) => Ok(Some(
+                tags.iter().filter_map(|tag| tag_version(tag)).collect(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(IfMatch(condition(&parts.headers, IF_MATCH)))
+    }
+}
+
+/// Extractor for the `If-None-Match` header of reads.
+#[derive(Debug, Clone)]
+pub struct IfNoneMatch(Option<Condition>);
+
+impl IfNoneMatch {
+    /// Whether the client already holds `version`, so a read can answer `304 Not Modified`.
+    /// Tags are compared weakly.
+    pub fn matches(&self, version: i32) -> bool {
+        match &self.0 {
+            None => false,
+            Some(Condition::Any) => true,
+            Some(Condition::Tags(tags)) => tags
+                .iter()
+                .any(|tag| tag_version(tag.strip_prefix("W/").unwrap_or(tag)) == Some(version)),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(IfNoneMatch(condition(&parts.headers, IF_NONE_MATCH)))
+    }
+}
@@ -0,0 +1,201 @@
+use sea_query::Values;
+use sea_query_binder::SqlxValues;
+use sqlx::{
+    postgres::{PgQueryResult, PgRow},
+    Executor, Postgres,
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::{field::Empty, Instrument};
+
+/// Statements taking at least this long are logged as slow, `0` turns the warning off.
+static SLOW_QUERY_THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_slow_query_threshold(threshold: Duration) {
+    SLOW_QUERY_THRESHOLD_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// A statement built with `build_sqlx`, run like `sqlx::query_with` but traced.
+///
+/// Each execution gets a child span following the OpenTelemetry database conventions:
+/// `db.system`, `db.operation`, `db.statement` and `db.rows_affected`, the rows changed or
+/// returned. The statement is recorded with its `$n` placeholders, so bound values never
+/// reach telemetry. Failures are logged and mark the span as failed, except a missing row,
+/// which handlers answer with a `404`.
+pub fn query_with(sql: &str, values: SqlxValues) -> Query<'_> {
+    Query { sql, values }
+}
+
+/// A statement without bound values, e.g. `SELECT 1`.
+pub fn query(sql: &str) -> Query<'_> {
+    query_with(sql, SqlxValues(Values(Vec::new())))
+}
+
+pub struct Query<'q> {
+    sql: &'q str,
+    values: SqlxValues,
+}
+
+/// A [`Query`] whose rows are mapped with `F`.
+pub struct Map<'q, F> {
+    query: Query<'q>,
+    mapper: F,
+}
+
+impl<'q> Query<'q> {
+    pub fn map<F, O>(self, mapper: F) -> Map<'q, F>
+    where
+        F: FnMut(PgRow) -> O + Send,
+        O: Send + Unpin,
+    {
+        Map {
+            query: self,
+            mapper,
+        }
+    }
+
+    pub async fn execute<'c, E>(self, executor: E) -> Result<PgQueryResult, sqlx::Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        let Self { sql, values } = self;
+
+        traced(
+            sql,
+            sqlx::query_with(sql, values).execute(executor),
+            PgQueryResult::rows_affected,
+        )
+        .await
+    }
+
+    pub async fn fetch_optional<'c, E>(self, executor: E) -> Result<Option<PgRow>, sqlx::Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        self.map(|row| row).fetch_optional(executor).await
+    }
+
+    pub async fn fetch_all<'c, E>(self, executor: E) -> Result<Vec<PgRow>, sqlx::Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        self.map(|row| row).fetch_all(executor).await
+    }
+}
+
+impl<'q, F, O> Map<'q, F>
+where
+    F: FnMut(PgRow) -> O + Send,
+    O: Send + Unpin,
+{
+    pub async fn fetch_one<'c, E>(self, executor: E) -> Result<O, sqlx::Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        let Query { sql, values } = self.query;
+
+        traced(
+            sql,
+            sqlx::query_with(sql, values)
+                .map(self.mapper)
+                .fetch_one(executor),
+            |_| 1,
+        )
+        .await
+    }
+
+    pub async fn fetch_optional<'c, E>(self, executor: E) -> Result<Option<O>, sqlx::Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        let Query { sql, values } = self.query;
+
+        traced(
+            sql,
+            sqlx::query_with(sql, values)
+                .map(self.mapper)
+                .fetch_optional(executor),
+            |row| u64::from(row.is_some()),
+        )
+        .await
+    }
+
+    pub async fn fetch_all<'c, E>(self, executor: E) -> Result<Vec<O>, sqlx::Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        let Query { sql, values } = self.query;
+
+        traced(
+            sql,
+            sqlx::query_with(sql, values)
+                .map(self.mapper)
+                .fetch_all(executor),
+            |rows| rows.len() as u64,
+        )
+        .await
+    }
+}
+
+async fn traced<T>(
+    sql: &str,
+    execution: impl Future<Output = Result<T, sqlx::Error>>,
+    rows: impl FnOnce(&T) -> u64,
+) -> Result<T, sqlx::Error> {
+    let operation = sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+
+    let span = tracing::info_span!(
+        "db-query",
+        otel.name = %operation,
+        otel.kind = "client",
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        db.system = "postgresql",
+        db.operation = %operation,
+        db.statement = sql,
+        db.rows_affected = Empty,
+    );
+
+    let started = Instant::now();
+
+    let result = execution.instrument(span.clone()).await;
+
+    let elapsed = started.elapsed();
+
+    match &result {
+        Ok(output) => {
+            // Recorded signed: the OpenTelemetry layer turns unsigned values into strings.
+            span.record("db.rows_affected", rows(output) as i64);
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            span.record("db.rows_affected", 0);
+        }
+        Err(error) => {
+            span.record("otel.status_code", "ERROR");
+
+            span.record("otel.status_message", error.to_string());
+
+            tracing::error!(parent: &span, "Failed to execute query: {:?}", error);
+        }
+    }
+
+    let threshold = SLOW_QUERY_THRESHOLD_MS.load(Ordering::Relaxed);
+
+    if threshold > 0 && elapsed >= Duration::from_millis(threshold) {
+        tracing::warn!(
+            parent: &span,
+            db.statement = sql,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "slow query"
+        );
+    }
+
+    result
+}
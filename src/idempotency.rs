@@ -0,0 +1,416 @@
+use crate::{auth::ClientIp, db, token, AppState};
+use axum::{
+    body::{self, Body, BoxBody, Bytes},
+    http::{header::AUTHORIZATION, HeaderName, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{DateTime, Duration, Utc};
+use hyper::body::HttpBody;
+use hyper::StatusCode;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgRow, PgPool, Row};
+use std::{net::IpAddr, sync::Arc};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Set on responses replayed from an earlier request with the same key.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Bodies are buffered to be fingerprinted, so they're capped well above any owner signup.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Iden)]
+pub enum IdempotencyKeys {
+    Table,
+    Principal,
+    Key,
+    Fingerprint,
+    StatusCode,
+    ResponseHeaders,
+    ResponseBody,
+    CreatedAt,
+    ExpiresAt,
+    LockedUntil,
+}
+
+/// A response stored for replay.
+#[derive(Debug)]
+pub struct StoredResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum Claim {
+    /// The key is new, its previous use expired, or the request holding it let its lease lapse
+    /// without finishing; the request should run.
+    Claimed,
+    /// The key is held by a request that hasn't finished.
+    InProgress,
+    /// The key was used for a different request.
+    Mismatch,
+    Completed(StoredResponse),
+}
+
+/// Identifies a request by what it would do: its method, target and body.
+fn fingerprint(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut digest = Sha256::new();
+
+    digest.update(method.as_str());
+
+    digest.update(b" ");
+
+    digest.update(uri);
+
+    digest.update(b"\n");
+
+    digest.update(body);
+
+    hex::encode(digest.finalize())
+}
+
+/// Keys are scoped to the caller's credentials so one client can't replay another's response.
+/// Anonymous callers are told apart by their IP.
+fn principal(req: &Request<Body>, ip: IpAddr) -> String {
+    match req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(authorization) => token::hash(authorization),
+        None => token::hash(&format!("ip:{}", ip)),
+    }
+}
+
+async fn read_body(mut body: Body) -> Result<Bytes, Response> {
+    let mut buffered = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|error| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read the request body: {}", error),
+            )
+                .into_response()
+        })?;
+
+        if buffered.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "Requests with an Idempotency-Key are limited to {} bytes",
+                    MAX_BODY_BYTES
+                ),
+            )
+                .into_response());
+        }
+
+        buffered.extend_from_slice(&chunk);
+    }
+
+    Ok(buffered.into())
+}
+
+fn stored_response_from_row(row: &PgRow) -> Option<StoredResponse> {
+    let status_code: Option<i16> = row.get("status_code");
+
+    let headers: Option<Value> = row.get("response_headers");
+
+    let body: Option<Vec<u8>> = row.get("response_body");
+
+    Some(StoredResponse {
+        status_code: TryFrom::try_from(status_code?).ok()?,
+        headers: serde_json::from_value(headers?).ok()?,
+        body: body?,
+    })
+}
+
+/// Reserves `key` for a request, or reports what an earlier request with it left behind. The
+/// reservation is a lease: once `lease` passes without a stored response, the key can be
+/// claimed again, since the request holding it was dropped or panicked.
+#[tracing::instrument(name = "CLAIM an idempotency key", skip(db))]
+pub async fn claim(
+    principal: &str,
+    key: &str,
+    fingerprint: &str,
+    ttl: Duration,
+    lease: Duration,
+    db: &PgPool,
+) -> Result<Claim, sqlx::Error> {
+    let (sql, values) = Query::insert()
+        .into_table(IdempotencyKeys::Table)
+        .columns([
+            IdempotencyKeys::Principal,
+            IdempotencyKeys::Key,
+            IdempotencyKeys::Fingerprint,
+            IdempotencyKeys::ExpiresAt,
+            IdempotencyKeys::LockedUntil,
+        ])
+        .values_panic([
+            principal.into(),
+            key.into(),
+            fingerprint.into(),
+            (Utc::now() + ttl).into(),
+            (Utc::now() + lease).into(),
+        ])
+        .on_conflict(
+            OnConflict::columns([IdempotencyKeys::Principal, IdempotencyKeys::Key])
+                .values([
+                    (IdempotencyKeys::Fingerprint, fingerprint.into()),
+                    (IdempotencyKeys::StatusCode, Option::<i16>::None.into()),
+                    (
+                        IdempotencyKeys::ResponseHeaders,
+                        Option::<Value>::None.into(),
+                    ),
+                    (
+                        IdempotencyKeys::ResponseBody,
+                        Option::<Vec<u8>>::None.into(),
+                    ),
+                    (IdempotencyKeys::CreatedAt, Expr::current_timestamp().into()),
+                    (IdempotencyKeys::ExpiresAt, (Utc::now() + ttl).into()),
+                    (IdempotencyKeys::LockedUntil, (Utc::now() + lease).into()),
+                ])
+                .action_and_where(
+                    Expr::col((IdempotencyKeys::Table, IdempotencyKeys::ExpiresAt))
+                        .lte(Expr::current_timestamp())
+                        .or(
+                            Expr::col((IdempotencyKeys::Table, IdempotencyKeys::StatusCode))
+                                .is_null()
+                                .and(
+                                    Expr::col((
+                                        IdempotencyKeys::Table,
+                                        IdempotencyKeys::LockedUntil,
+                                    ))
+                                    .lte(Expr::current_timestamp()),
+                                ),
+                        ),
+                )
+                .to_owned(),
+        )
+        .returning(Query::returning().columns([IdempotencyKeys::Key]))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let claimed = db::query_with(&sql, values).fetch_optional(db).await?;
+
+    if claimed.is_some() {
+        return Ok(Claim::Claimed);
+    }
+
+    let (sql, values) = Query::select()
+        .columns([
+            IdempotencyKeys::Fingerprint,
+            IdempotencyKeys::StatusCode,
+            IdempotencyKeys::ResponseHeaders,
+            IdempotencyKeys::ResponseBody,
+        ])
+        .from(IdempotencyKeys::Table)
+        .and_where(Expr::col(IdempotencyKeys::Principal).eq(principal))
+        .and_where(Expr::col(IdempotencyKeys::Key).eq(key))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let Some(row) = db::query_with(&sql, values).fetch_optional(db).await? else {
+        // Released by a failed request in the meantime; the client may simply retry.
+        return Ok(Claim::InProgress);
+    };
+
+    let stored_fingerprint: String = row.get("fingerprint");
+
+    if stored_fingerprint != fingerprint {
+        return Ok(Claim::Mismatch);
+    }
+
+    Ok(match stored_response_from_row(&row) {
+        Some(response) => Claim::Completed(response),
+        None => Claim::InProgress,
+    })
+}
+
+#[tracing::instrument(name = "STORE an idempotent response", skip(response, db))]
+pub async fn complete(
+    principal: &str,
+    key: &str,
+    response: &StoredResponse,
+    db: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let headers = serde_json::to_value(&response.headers).unwrap_or_default();
+
+    let (sql, values) = Query::update()
+        .table(IdempotencyKeys::Table)
+        .value(IdempotencyKeys::StatusCode, response.status_code as i16)
+        .value(IdempotencyKeys::ResponseHeaders, headers)
+        .value(IdempotencyKeys::ResponseBody, response.body.clone())
+        .value(IdempotencyKeys::LockedUntil, Option::<DateTime<Utc>>::None)
+        .and_where(Expr::col(IdempotencyKeys::Principal).eq(principal))
+        .and_where(Expr::col(IdempotencyKeys::Key).eq(key))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(db).await.map(|_| ())
+}
+
+/// Frees `key` so the request can be retried, e.g. after a server error.
+#[tracing::instrument(name = "RELEASE an idempotency key", skip(db))]
+pub async fn release(principal: &str, key: &str, db: &PgPool) -> Result<(), sqlx::Error> {
+    let (sql, values) = Query::delete()
+        .from_table(IdempotencyKeys::Table)
+        .and_where(Expr::col(IdempotencyKeys::Principal).eq(principal))
+        .and_where(Expr::col(IdempotencyKeys::Key).eq(key))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(db).await.map(|_| ())
+}
+
+#[tracing::instrument(name = "PRUNE expired idempotency keys", skip(db))]
+pub async fn prune_expired(now: DateTime<Utc>, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let (sql, values) = Query::delete()
+        .from_table(IdempotencyKeys::Table)
+        .and_where(Expr::col(IdempotencyKeys::ExpiresAt).lte(now))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(body::boxed(Body::from(stored.body)));
+
+    *response.status_mut() = StatusCode::from_u16(stored.status_code).unwrap_or(StatusCode::OK);
+
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            response.headers_mut().append(name, value);
+        }
+    }
+
+    response.headers_mut().insert(
+        HeaderName::from_static(IDEMPOTENT_REPLAYED),
+        HeaderValue::from_static("true"),
+    );
+
+    response
+}
+
+fn unknown_error(error: impl std::fmt::Debug) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Unknown Error: {:?}", error),
+    )
+        .into_response()
+}
+
+/// Makes requests carrying an `Idempotency-Key` header safe to retry. Only layered on routes
+/// that create resources, currently `POST /owner`: responses are stored in plaintext, so routes
+/// issuing credentials (sessions, API keys, webhook secrets) must never be wrapped.
+///
+/// The first request with a key runs and its response is stored for `idempotency_key_ttl_hours`.
+/// Retries with the same key and body get that response back instead of running again; reusing
+/// the key for a different request is rejected with `422`. Server errors aren't stored, so
+/// the request can be retried with the same key. Retries while the first request runs get
+/// `409`, until its `idempotency_key_lease_secs` lease runs out.
+pub async fn middleware(
+    ClientIp(ip): ClientIp,
+    Extension(state): Extension<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let Some(key) = req.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(req).await;
+    };
+
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_KEY_LENGTH
+                ),
+            )
+                .into_response()
+        }
+    };
+
+    let principal = principal(&req, ip);
+
+    let (parts, body) = req.into_parts();
+
+    let body = match read_body(body).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    let fingerprint = fingerprint(&parts.method, &parts.uri.to_string(), &body);
+
+    let ttl = Duration::hours(state.config.idempotency_key_ttl_hours);
+
+    let lease = Duration::seconds(state.config.idempotency_key_lease_secs);
+
+    match claim(&principal, &key, &fingerprint, ttl, lease, &state.db).await {
+        Ok(Claim::Claimed) => {}
+        Ok(Claim::InProgress) => {
+            return (
+                StatusCode::CONFLICT,
+                String::from("A request with this Idempotency-Key is still being processed"),
+            )
+                .into_response()
+        }
+        Ok(Claim::Mismatch) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                String::from("Idempotency-Key was already used for a different request"),
+            )
+                .into_response()
+        }
+        Ok(Claim::Completed(stored)) => return replay(stored),
+        Err(error) => return unknown_error(error),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    if response.status().is_server_error() {
+        if let Err(error) = release(&principal, &key, &state.db).await {
+            tracing::error!("Failed to release idempotency key: {:?}", error);
+        }
+
+        return response;
+    }
+
+    let (parts, body): (_, BoxBody) = response.into_parts();
+
+    let body: Bytes = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            if let Err(error) = release(&principal, &key, &state.db).await {
+                tracing::error!("Failed to release idempotency key: {:?}", error);
+            }
+
+            return unknown_error(error);
+        }
+    };
+
+    let stored = StoredResponse {
+        status_code: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: body.to_vec(),
+    };
+
+    if let Err(error) = complete(&principal, &key, &stored, &state.db).await {
+        tracing::error!("Failed to store idempotent response: {:?}", error);
+    }
+
+    Response::from_parts(parts, body::boxed(Body::from(body)))
+}
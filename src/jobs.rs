@@ -0,0 +1,174 @@
+use crate::{
+    api::{
+        owner::purge_deleted_owners,
+        webhook::{self, DeliveryPolicy},
+    },
+    idempotency,
+    lockout::LoginThrottle,
+    metrics,
+    outbox::{self, DispatchPolicy, DomainEvent, EventSink},
+};
+use chrono::Utc;
+use opentelemetry::metrics::Histogram;
+use sqlx::PgPool;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::broadcast, task::JoinHandle, time};
+
+/// Periodically hard-deletes owners whose soft delete is older than the retention window.
+pub fn spawn_owner_purge(db: PgPool, retention_days: i64, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match purge_deleted_owners(retention_days, &db).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "purged soft-deleted owners"),
+                Err(error) => tracing::error!("Failed to purge soft-deleted owners: {:?}", error),
+            }
+        }
+    })
+}
+
+/// Periodically deletes idempotency keys past their TTL.
+pub fn spawn_idempotency_key_prune(db: PgPool, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match idempotency::prune_expired(Utc::now(), &db).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!(pruned, "pruned expired idempotency keys"),
+                Err(error) => tracing::error!("Failed to prune idempotency keys: {:?}", error),
+            }
+        }
+    })
+}
+
+/// Periodically deletes outbox events dispatched more than `retention_days` ago.
+pub fn spawn_outbox_prune(db: PgPool, retention_days: i64, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            let before = Utc::now() - chrono::Duration::days(retention_days);
+
+            match outbox::prune_dispatched(before, &db).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!(pruned, "pruned dispatched outbox events"),
+                Err(error) => tracing::error!("Failed to prune outbox events: {:?}", error),
+            }
+        }
+    })
+}
+
+/// Publishes queued outbox events, draining full batches before waiting for the next tick.
+pub fn spawn_outbox_dispatcher(
+    db: PgPool,
+    sink: Arc<dyn EventSink>,
+    policy: DispatchPolicy,
+    every: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match outbox::dispatch(sink.as_ref(), policy, &db).await {
+                    Ok(delivered) if delivered as u64 == policy.batch_size => continue,
+                    Ok(_) => break,
+                    Err(error) => {
+                        tracing::error!("Failed to dispatch outbox events: {:?}", error);
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Feeds committed outbox events to the live event streams, reconnecting after errors.
+pub fn spawn_event_relay(
+    db: PgPool,
+    sender: broadcast::Sender<Arc<DomainEvent>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(error) = outbox::relay_notifications(&sender, &db).await {
+                tracing::error!("Failed to relay outbox notifications: {:?}", error);
+            }
+
+            time::sleep(Duration::from_secs(1)).await;
+        }
+    })
+}
+
+/// Sends due webhook deliveries, draining full batches before waiting for the next tick.
+pub fn spawn_webhook_dispatcher(
+    db: PgPool,
+    policy: DeliveryPolicy,
+    every: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match webhook::dispatch(policy, &db).await {
+                    Ok(attempted) if attempted as u64 == policy.batch_size => continue,
+                    Ok(_) => break,
+                    Err(error) => {
+                        tracing::error!("Failed to dispatch webhook deliveries: {:?}", error);
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Periodically drops stale failed-login counters so the throttle doesn't grow unbounded.
+pub fn spawn_login_throttle_prune(throttle: Arc<LoginThrottle>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            throttle.prune();
+        }
+    })
+}
+
+/// Samples how long it takes to get a pool connection. sqlx doesn't report the waits of
+/// queries themselves, so this shows whether requests would queue at the time of each tick.
+pub fn spawn_pool_probe(db: PgPool, wait_time: Histogram<f64>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            let started = Instant::now();
+
+            match db.acquire().await {
+                Ok(_) => {
+                    metrics::record_pool_wait(&wait_time, started.elapsed().as_secs_f64() * 1000.0)
+                }
+                Err(error) => tracing::warn!("Failed to acquire a pool connection: {:?}", error),
+            }
+        }
+    })
+}
@@ -1,8 +1,11 @@
-use axum::{routing::IntoMakeService, Extension, Router, Server};
+use axum::{
+    extract::connect_info::IntoMakeServiceWithConnectInfo, middleware, Extension, Router, Server,
+};
 use http::header::HeaderName;
 use hyper::server::conn::AddrIncoming;
 use sqlx::PgPool;
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
 use tower::ServiceBuilder;
 use tower_http::{
     request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
@@ -12,38 +15,182 @@ use tracing::Level;
 
 mod api;
 
+mod audit;
+
+mod auth;
+
+mod conditional;
+
+mod db;
+
+mod idempotency;
+
+mod jobs;
+
+mod lockout;
+
+mod logging;
+
+mod mailer;
+
+mod merge_patch;
+
+mod metrics;
+
+mod outbox;
+
+mod password;
+
+mod rbac;
+
+mod redact;
+
 mod settings;
 
 mod telemetry;
 
+mod token;
+
 pub use settings::Settings;
 
+pub use mailer::{Email, FileMailer, InMemoryMailer, Mailer, MailerKind, SmtpMailer};
+
+pub use outbox::{
+    prune_dispatched as prune_dispatched_events, DomainEvent, EventSink, EventSinkKind, FileSink,
+    StdoutSink, WebhookSink,
+};
+
 pub use api::owner::{
-    ApiPayload, CreateOwner, CreateOwnerResponse, Owners, OwnersIden, UpdateCredentials,
+    ApiPayload, CreateOwner, CreateOwnerResponse, Owners, OwnersIden, UpdateEmail, UpdatePassword,
     UpdateProfile,
 };
 
-pub use api::owner::create_owner;
+pub use api::owner::{create_owner, purge_deleted_owners};
+
+pub use api::admin::UpdateLogLevel;
+
+pub use api::api_key::{ApiKey, CreateApiKey, CreateApiKeyResponse};
+
+pub use api::health_check::{ComponentHealth, HealthStatus, Readiness};
+
+pub use api::password_reset::{ConfirmPasswordReset, RequestPasswordReset};
+
+pub use api::privacy::OwnerExport;
+
+pub use api::session::{CreateSession, CreateSessionResponse};
+
+pub use api::webhook::{
+    CreateWebhook, CreateWebhookResponse, DeliveryAttempt, DeliveryStatus, UpdateWebhook, Webhook,
+    WebhookDelivery,
+};
+
+pub use audit::{Actor, AuditRecord};
+
+pub use password::hash as hash_password;
+
+pub use telemetry::{init as init_telemetry, MetricsExporter, TelemetryExporter, TelemetryGuard};
+
+pub use logging::{LogFormat, LogLevelStatus, LogRotation};
+
+pub use rbac::{grant_role, Permission, Role, Scope};
+
+pub use redact::RedactionPolicy;
 
 #[allow(unused)]
 #[derive(Debug)]
 pub struct AppState {
     db: PgPool,
     config: Settings,
+    mailer: Arc<dyn mailer::Mailer>,
+    login_throttle: Arc<lockout::LoginThrottle>,
+    events: broadcast::Sender<Arc<DomainEvent>>,
+    http_metrics: metrics::HttpMetrics,
 }
 
 pub fn serve(
     addr: &SocketAddr,
     db: PgPool,
     config: Settings,
-) -> Server<AddrIncoming, IntoMakeService<axum::Router>> {
+) -> Server<AddrIncoming, IntoMakeServiceWithConnectInfo<axum::Router, SocketAddr>> {
+    let mailer = mailer::from_settings(&config).expect("Failed to configure mailer");
+
+    let redaction = Arc::new(redact::RedactionPolicy::from_settings(&config));
+
+    redact::install(redaction.clone());
+
+    db::set_slow_query_threshold(Duration::from_millis(config.db_slow_query_threshold_ms));
+
+    let login_throttle = Arc::new(lockout::LoginThrottle::new(
+        lockout::LockoutPolicy::from_settings(&config),
+    ));
+
+    jobs::spawn_login_throttle_prune(login_throttle.clone(), Duration::from_secs(60));
+
     let x_request_id = HeaderName::from_static("x-request-id");
 
+    jobs::spawn_owner_purge(
+        db.clone(),
+        config.owner_retention_days,
+        Duration::from_secs(config.owner_purge_interval_secs),
+    );
+
+    jobs::spawn_idempotency_key_prune(db.clone(), Duration::from_secs(3600));
+
+    jobs::spawn_outbox_prune(
+        db.clone(),
+        config.outbox_retention_days,
+        Duration::from_secs(3600),
+    );
+
+    let event_sink = outbox::from_settings(&config).expect("Failed to configure event sink");
+
+    jobs::spawn_outbox_dispatcher(
+        db.clone(),
+        event_sink,
+        outbox::DispatchPolicy::from_settings(&config),
+        Duration::from_millis(config.outbox_dispatch_interval_ms),
+    );
+
+    let meter = metrics::meter();
+
+    let http_metrics = metrics::HttpMetrics::new(&meter);
+
+    if let Err(error) = metrics::observe_pool(&meter, db.clone(), config.db_max_connections) {
+        tracing::error!("Failed to observe the connection pool: {:?}", error);
+    }
+
+    jobs::spawn_pool_probe(
+        db.clone(),
+        metrics::pool_wait_time(&meter),
+        Duration::from_secs(config.db_pool_probe_interval_secs),
+    );
+
+    let (events, _) = broadcast::channel(1024);
+
+    jobs::spawn_event_relay(db.clone(), events.clone());
+
+    jobs::spawn_webhook_dispatcher(
+        db.clone(),
+        api::webhook::DeliveryPolicy::from_settings(&config),
+        Duration::from_millis(config.webhook_dispatch_interval_ms),
+    );
+
     let app = Router::new()
+        .merge(api::admin::router())
+        .merge(api::api_key::router())
+        .merge(api::events::router())
         .merge(api::health_check::router())
+        .merge(api::metrics::router())
         .merge(api::owner::router())
+        .merge(api::password_reset::router())
+        .merge(api::privacy::router())
+        .merge(api::session::router())
+        .merge(api::verification::router())
+        .merge(api::webhook::router())
+        .layer(middleware::from_fn(metrics::record_request))
         .layer(
             ServiceBuilder::new()
+                .map_request(telemetry::discard_invalid_request_id)
                 .layer(SetRequestIdLayer::new(
                     x_request_id.clone(),
                     telemetry::HTTPRequestId::default(),
@@ -51,13 +198,20 @@ pub fn serve(
                 .layer(PropagateRequestIdLayer::new(x_request_id))
                 .layer(
                     TraceLayer::new_for_http()
-                        .make_span_with(telemetry::InitialSpan::new())
+                        .make_span_with(telemetry::InitialSpan::new(redaction))
                         .on_request(DefaultOnRequest::new().level(Level::INFO))
                         .on_response(telemetry::OnResponseTrace)
-                        .on_failure(telemetry::OnFailureTrace),
+                        .on_failure(telemetry::OnFailureTrace::new(&meter)),
                 )
-                .layer(Extension(Arc::new(AppState { db, config }))),
+                .layer(Extension(Arc::new(AppState {
+                    db,
+                    config,
+                    mailer,
+                    login_throttle,
+                    events,
+                    http_metrics,
+                }))),
         );
 
-    axum::Server::bind(addr).serve(app.into_make_service())
+    axum::Server::bind(addr).serve(app.into_make_service_with_connect_info::<SocketAddr>())
 }
@@ -0,0 +1,150 @@
+use crate::Settings;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Tunables for [`LoginThrottle`], read from `Settings`.
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    pub max_account_failures: u32,
+    pub max_ip_failures: u32,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    pub lockout_duration: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn from_settings(config: &Settings) -> Self {
+        Self {
+            max_account_failures: config.login_max_account_failures,
+            max_ip_failures: config.login_max_ip_failures,
+            backoff_base: Duration::from_millis(config.login_backoff_base_ms),
+            backoff_max: Duration::from_millis(config.login_backoff_max_ms),
+            lockout_duration: Duration::from_secs(config.login_lockout_minutes * 60),
+        }
+    }
+
+    /// Delay imposed after the `failures`-th consecutive failure: base · 2^(failures - 1), capped.
+    fn backoff(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(31);
+
+        self.backoff_base
+            .saturating_mul(1 << exponent)
+            .min(self.backoff_max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThrottleKey {
+    Account(String),
+    Ip(IpAddr),
+}
+
+impl ThrottleKey {
+    pub fn account(email: &str) -> Self {
+        ThrottleKey::Account(email.trim().to_lowercase())
+    }
+}
+
+#[derive(Debug)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    blocked_until: Instant,
+}
+
+/// Failed-login bookkeeping shared by every request of this instance.
+///
+/// Counters are kept per account and per client IP. Each failure blocks the account for an
+/// exponentially growing delay; reaching either failure limit locks the key for
+/// `lockout_duration`. State is per instance, so limits apply to each replica separately.
+#[derive(Debug)]
+pub struct LoginThrottle {
+    policy: LockoutPolicy,
+    attempts: Mutex<HashMap<ThrottleKey, Attempts>>,
+}
+
+impl LoginThrottle {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns how long the caller must wait if any of `keys` is currently blocked.
+    pub fn check(&self, keys: &[ThrottleKey]) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        let attempts = self.attempts.lock().unwrap();
+
+        let retry_after = keys
+            .iter()
+            .filter_map(|key| attempts.get(key))
+            .filter(|entry| entry.blocked_until > now)
+            .map(|entry| entry.blocked_until - now)
+            .max();
+
+        match retry_after {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a failed attempt against every key. Returns the keys that just became locked.
+    pub fn record_failure(&self, keys: &[ThrottleKey]) -> Vec<ThrottleKey> {
+        let now = Instant::now();
+
+        let mut attempts = self.attempts.lock().unwrap();
+
+        let mut locked = Vec::new();
+
+        for key in keys {
+            let entry = attempts.entry(key.clone()).or_insert(Attempts {
+                failures: 0,
+                last_failure: now,
+                blocked_until: now,
+            });
+
+            entry.failures += 1;
+            entry.last_failure = now;
+
+            let limit = match key {
+                ThrottleKey::Account(_) => self.policy.max_account_failures,
+                ThrottleKey::Ip(_) => self.policy.max_ip_failures,
+            };
+
+            if entry.failures >= limit {
+                entry.blocked_until = now + self.policy.lockout_duration;
+
+                if entry.failures == limit {
+                    locked.push(key.clone());
+                }
+            } else if let ThrottleKey::Account(_) = key {
+                // Shared addresses (NAT, proxies) only get the hard limit, not the backoff.
+                entry.blocked_until = now + self.policy.backoff(entry.failures);
+            }
+        }
+
+        locked
+    }
+
+    /// Clears the counters of `key`, e.g. after a successful login or an admin unlock.
+    pub fn reset(&self, key: &ThrottleKey) -> bool {
+        self.attempts.lock().unwrap().remove(key).is_some()
+    }
+
+    /// Forgets keys that are no longer blocked and haven't failed for a full lockout period.
+    pub fn prune(&self) {
+        let now = Instant::now();
+
+        let lockout_duration = self.policy.lockout_duration;
+
+        self.attempts.lock().unwrap().retain(|_, entry| {
+            entry.blocked_until > now || now - entry.last_failure < lockout_duration
+        });
+    }
+}
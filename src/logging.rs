@@ -0,0 +1,393 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use opentelemetry::trace::TraceContextExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    fmt::{
+        format::{JsonFields, Writer},
+        FmtContext, FormatEvent, FormattedFields, MakeWriter,
+    },
+    layer::Layered,
+    registry::{LookupSpan, SpanRef},
+    reload, EnvFilter, Layer, Registry,
+};
+
+use crate::Settings;
+
+/// A log output, boxed so outputs of different formats and writers can be stacked.
+pub type LogLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// The subscriber the filter sits on: every log output and the OpenTelemetry layer.
+type Outputs = Layered<Vec<LogLayer>, Registry>;
+
+/// The log filter, wrapped so [`LogLevel`] can swap its directives at runtime.
+pub type ReloadableFilter = reload::Layer<EnvFilter, Outputs>;
+
+static LOG_LEVEL: OnceLock<LogLevel> = OnceLock::new();
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Multi-line and colored, for reading in a terminal.
+    Pretty,
+    /// One line per event, still meant for humans.
+    Compact,
+    /// One JSON object per event, with the `trace_id` and `span_id` of the span it happened
+    /// in, for log collectors.
+    Json,
+}
+
+/// When the log file starts over in a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Which events get logged: the `RUST_LOG` directives when that variable is set, so a single
+/// run can be made verbose without touching the configuration, `log_level` otherwise.
+fn filter(config: &Settings) -> anyhow::Result<EnvFilter> {
+    match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::try_new(&directives)
+            .map_err(|error| anyhow::anyhow!("Invalid RUST_LOG {:?}: {}", directives, error)),
+        Err(_) => EnvFilter::try_new(&config.log_level).map_err(|error| {
+            anyhow::anyhow!("Invalid log_level {:?}: {}", config.log_level, error)
+        }),
+    }
+}
+
+/// [`filter`], made reloadable. Pass the [`LogLevel`] to [`install`] once the subscriber the
+/// filter is part of is the global default.
+pub fn reloadable_filter(config: &Settings) -> anyhow::Result<(ReloadableFilter, LogLevel)> {
+    let filter = filter(config)?;
+
+    let configured = filter.to_string();
+
+    let (layer, handle) = reload::Layer::new(filter);
+
+    Ok((
+        layer,
+        LogLevel {
+            handle,
+            configured,
+            revert: Mutex::default(),
+        },
+    ))
+}
+
+pub fn install(level: LogLevel) -> anyhow::Result<()> {
+    LOG_LEVEL
+        .set(level)
+        .map_err(|_| anyhow::anyhow!("The log level is already installed"))
+}
+
+/// The process' log level, once [`install`]ed.
+pub fn log_level() -> Option<&'static LogLevel> {
+    LOG_LEVEL.get()
+}
+
+/// Directives in effect, and when they go back to the configured ones.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct LogLevelStatus {
+    pub directives: String,
+    pub revert_at: Option<DateTime<Utc>>,
+}
+
+/// Controls which events get logged while the process runs, e.g. to debug one module during
+/// an incident without restarting.
+pub struct LogLevel {
+    handle: reload::Handle<EnvFilter, Outputs>,
+    /// Directives the process started with, restored by a revert.
+    configured: String,
+    revert: Mutex<Revert>,
+}
+
+/// The revert still to come, if any. Each change bumps the generation, so a revert that's
+/// already running when a newer change lands doesn't undo that one.
+#[derive(Default)]
+struct Revert {
+    generation: u64,
+    pending: Option<(DateTime<Utc>, JoinHandle<()>)>,
+}
+
+impl LogLevel {
+    pub fn status(&self) -> LogLevelStatus {
+        LogLevelStatus {
+            directives: self
+                .handle
+                .with_current(ToString::to_string)
+                .unwrap_or_default(),
+            revert_at: self
+                .revert
+                .lock()
+                .unwrap()
+                .pending
+                .as_ref()
+                .map(|(at, _)| *at),
+        }
+    }
+
+    /// Replaces the filter. With `revert_after`, the configured directives come back after that
+    /// long. Any change cancels the revert pending from an earlier one.
+    pub fn set(
+        &'static self,
+        filter: EnvFilter,
+        revert_after: Option<Duration>,
+    ) -> Result<LogLevelStatus, reload::Error> {
+        let mut revert = self.revert.lock().unwrap();
+
+        self.handle.reload(filter)?;
+
+        revert.generation += 1;
+
+        if let Some((_, task)) = revert.pending.take() {
+            task.abort();
+        }
+
+        if let Some(after) = revert_after {
+            let generation = revert.generation;
+
+            let at = Utc::now()
+                + chrono::Duration::from_std(after)
+                    .unwrap_or_else(|_| chrono::Duration::max_value());
+
+            let task = tokio::spawn(async move {
+                tokio::time::sleep(after).await;
+
+                self.revert(generation);
+            });
+
+            revert.pending = Some((at, task));
+        }
+
+        drop(revert);
+
+        Ok(self.status())
+    }
+
+    fn revert(&self, generation: u64) {
+        let mut revert = self.revert.lock().unwrap();
+
+        if revert.generation != generation {
+            return;
+        }
+
+        revert.pending = None;
+
+        let result = EnvFilter::try_new(&self.configured)
+            .map_err(anyhow::Error::from)
+            .and_then(|filter| Ok(self.handle.reload(filter)?));
+
+        match result {
+            Ok(()) => tracing::warn!(
+                target: "audit",
+                event = "log_level_reverted",
+                directives = %self.configured,
+                "log level reverted"
+            ),
+            Err(error) => tracing::error!("Failed to revert the log level: {:?}", error),
+        }
+    }
+}
+
+/// The outputs logs are written to: stdout and, when `log_file_dir` is set, a file rotated
+/// every `log_file_rotation`. File writes happen on a background thread that flushes when
+/// the returned guard is dropped.
+pub fn layers(config: &Settings) -> anyhow::Result<(Vec<LogLayer>, Option<WorkerGuard>)> {
+    let mut layers = vec![layer(config.log_format, std::io::stdout, true)];
+
+    let mut guard = None;
+
+    if let Some(dir) = &config.log_file_dir {
+        let appender = RollingFileAppender::builder()
+            .rotation(config.log_file_rotation.into())
+            .filename_prefix(&config.log_file_prefix)
+            .build(dir)?;
+
+        let (writer, worker) = tracing_appender::non_blocking(appender);
+
+        layers.push(layer(config.log_format, writer, false));
+
+        guard = Some(worker);
+    }
+
+    Ok((layers, guard))
+}
+
+fn layer<W>(format: LogFormat, writer: W, ansi: bool) -> LogLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match format {
+        LogFormat::Pretty => layer
+            .pretty()
+            .with_target(false)
+            .with_thread_ids(true)
+            .with_thread_names(true)
+            .boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Json => layer
+            .fmt_fields(JsonFields::new())
+            .event_format(JsonFormat)
+            .boxed(),
+    }
+}
+
+/// Writes an event as one JSON object: its time, level, target and fields, the spans it
+/// happened in with their fields, and the OpenTelemetry ids of the innermost one.
+struct JsonFormat;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+
+        let mut fields = Map::new();
+
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let mut line = Map::new();
+
+        line.insert(
+            "timestamp".into(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+                .into(),
+        );
+
+        line.insert("level".into(), metadata.level().as_str().into());
+
+        line.insert("target".into(), metadata.target().into());
+
+        line.insert("fields".into(), fields.into());
+
+        if let Some(span) = ctx.lookup_current() {
+            if let Some((trace_id, span_id)) = otel_ids(&span) {
+                line.insert("trace_id".into(), trace_id.into());
+
+                line.insert("span_id".into(), span_id.into());
+            }
+
+            line.insert("span".into(), span_fields(&span));
+
+            line.insert(
+                "spans".into(),
+                span.scope()
+                    .from_root()
+                    .map(|span| span_fields(&span))
+                    .collect(),
+            );
+        }
+
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// The span's name and the fields recorded on it so far.
+fn span_fields<S>(span: &SpanRef<'_, S>) -> Value
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let mut fields = span
+        .extensions()
+        .get::<FormattedFields<JsonFields>>()
+        .and_then(|fields| serde_json::from_str::<Map<String, Value>>(fields).ok())
+        .unwrap_or_default();
+
+    fields.insert("name".into(), span.name().into());
+
+    Value::Object(fields)
+}
+
+/// Trace and span id the OpenTelemetry layer assigned to the span, as exported. The trace id
+/// is the parent's when the span continues a trace, e.g. one extracted from `traceparent`.
+fn otel_ids<S>(span: &SpanRef<'_, S>) -> Option<(String, String)>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let extensions = span.extensions();
+
+    let data = extensions.get::<OtelData>()?;
+
+    let parent = data.parent_cx.span();
+
+    let trace_id = if parent.span_context().is_valid() {
+        parent.span_context().trace_id()
+    } else {
+        data.builder.trace_id?
+    };
+
+    let span_id = data.builder.span_id?;
+
+    Some((trace_id.to_string(), span_id.to_string()))
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::Settings;
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerKind {
+    Smtp,
+    File,
+    Memory,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing mail transport. Implementations must be cheap to share across requests.
+#[async_trait]
+pub trait Mailer: fmt::Debug + Send + Sync {
+    async fn send(&self, email: Email) -> anyhow::Result<()>;
+}
+
+/// Builds the mailer selected by `mailer_kind`.
+pub fn from_settings(config: &Settings) -> anyhow::Result<Arc<dyn Mailer>> {
+    let mailer: Arc<dyn Mailer> = match config.mailer_kind {
+        MailerKind::Smtp => Arc::new(SmtpMailer::new(config)?),
+        MailerKind::File => Arc::new(FileMailer::new(&config.mailer_file_dir)),
+        MailerKind::Memory => Arc::new(InMemoryMailer::default()),
+    };
+
+    Ok(mailer)
+}
+
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &Settings) -> anyhow::Result<Self> {
+        let mut transport =
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?
+                .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            transport = transport.credentials(Credentials::new(
+                username.clone(),
+                password.expose_secret().clone(),
+            ));
+        }
+
+        Ok(Self {
+            from: config.mailer_from.parse()?,
+            transport: transport.build(),
+        })
+    }
+}
+
+impl fmt::Debug for SmtpMailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpMailer")
+            .field("from", &self.from)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(email.subject)
+            .body(email.body)?;
+
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
+
+/// Writes every email as a JSON file into `dir`. Useful for local development and tests.
+#[derive(Debug, Clone)]
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let path = self.dir.join(format!(
+            "{}-{}.json",
+            chrono::Utc::now().format("%Y%m%d%H%M%S%f"),
+            Uuid::new_v4()
+        ));
+
+        tokio::fs::write(path, serde_json::to_vec_pretty(&email)?).await?;
+
+        Ok(())
+    }
+}
+
+/// Keeps sent emails in memory.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryMailer {
+    sent: Arc<Mutex<Vec<Email>>>,
+}
+
+impl InMemoryMailer {
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push(email);
+
+        Ok(())
+    }
+}
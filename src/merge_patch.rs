@@ -0,0 +1,88 @@
+use axum::{async_trait, body::Bytes, extract::FromRequest, BoxError};
+use http::{header::CONTENT_TYPE, Request};
+use hyper::{body::HttpBody, StatusCode};
+use serde_json::{Map, Value};
+
+type Rejection = (StatusCode, String);
+
+pub const MEDIA_TYPE: &str = "application/merge-patch+json";
+
+/// Extractor for a JSON Merge Patch (RFC 7396) body sent as `application/merge-patch+json`.
+///
+/// Only object patches are accepted, since a bare value would replace the whole resource.
+#[derive(Debug, Clone)]
+pub struct MergePatch(pub Map<String, Value>);
+
+impl MergePatch {
+    /// Applies the patch to `target`: `null` removes a member, objects merge recursively and
+    /// anything else replaces the member.
+    pub fn apply(&self, target: &mut Value) {
+        merge(target, &self.0);
+    }
+}
+
+fn merge(target: &mut Value, patch: &Map<String, Value>) {
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    let Value::Object(fields) = target else {
+        return;
+    };
+
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                fields.remove(key);
+            }
+            Value::Object(nested) => merge(fields.entry(key).or_insert(Value::Null), nested),
+            value => {
+                fields.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for MergePatch
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let is_merge_patch = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|media_type| media_type.trim().eq_ignore_ascii_case(MEDIA_TYPE))
+            .unwrap_or(false);
+
+        if !is_merge_patch {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Expected request with `Content-Type: {}`", MEDIA_TYPE),
+            ));
+        }
+
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|error| (StatusCode::BAD_REQUEST, error.to_string()))?;
+
+        match serde_json::from_slice(&body) {
+            Ok(Value::Object(patch)) => Ok(MergePatch(patch)),
+            Ok(_) => Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                String::from("Merge patch must be a JSON object"),
+            )),
+            Err(error) => Err((
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse the request body as JSON: {}", error),
+            )),
+        }
+    }
+}
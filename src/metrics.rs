@@ -0,0 +1,174 @@
+use crate::AppState;
+use axum::{
+    extract::MatchedPath,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Meter, MetricsError, Unit},
+    Context, KeyValue,
+};
+use sqlx::PgPool;
+use std::{sync::Arc, time::Instant};
+
+/// Name of the meter every instrument of this service is created from.
+pub const METER_NAME: &str = "proximity-service";
+
+/// Upper bounds of the duration histogram buckets, in milliseconds.
+pub const DURATION_BUCKETS_MS: [f64; 12] = [
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+/// Label of the pool in the `pool.name` attribute.
+const POOL_NAME: &str = "primary";
+
+pub fn meter() -> Meter {
+    global::meter(METER_NAME)
+}
+
+/// Rate, errors and duration of the requests this server handles.
+#[derive(Debug, Clone)]
+pub struct HttpMetrics {
+    requests: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl HttpMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            requests: meter
+                .u64_counter("http.server.requests")
+                .with_description("Requests handled")
+                .init(),
+            errors: meter
+                .u64_counter("http.server.errors")
+                .with_description("Requests answered with a 5xx status")
+                .init(),
+            duration: meter
+                .f64_histogram("http.server.duration")
+                .with_description("Time to produce the response head")
+                .with_unit(Unit::new("ms"))
+                .init(),
+        }
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Records every request, labelled by route template rather than path so ids in URLs don't
+/// create a series each. Requests matching no route share the `unmatched` label.
+///
+/// Only 5xx answers count as errors: 4xx are the client's, and alerting on them would page
+/// us for bad input.
+pub async fn record_request<B>(
+    Extension(state): Extension<Arc<AppState>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+
+    let method = req.method().to_string();
+
+    let started = Instant::now();
+
+    let response = next.run(req).await;
+
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let attributes = [
+        KeyValue::new("http.route", route),
+        KeyValue::new("http.method", method),
+        KeyValue::new("http.status_class", status_class(response.status())),
+    ];
+
+    let cx = Context::current();
+
+    let metrics = &state.http_metrics;
+
+    metrics.requests.add(&cx, 1, &attributes);
+
+    if response.status().is_server_error() {
+        metrics.errors.add(&cx, 1, &attributes);
+    }
+
+    metrics.duration.record(&cx, elapsed_ms, &attributes);
+
+    response
+}
+
+/// Reports the pool's connections, used and idle, each time metrics are collected.
+pub fn observe_pool(meter: &Meter, db: PgPool, max_connections: u32) -> Result<(), MetricsError> {
+    let usage = meter
+        .u64_observable_gauge("db.client.connections.usage")
+        .with_description("Connections in the pool, by state")
+        .init();
+
+    let max = meter
+        .u64_observable_gauge("db.client.connections.max")
+        .with_description("Most connections the pool opens")
+        .init();
+
+    meter.register_callback(move |cx| {
+        let size = u64::from(db.size());
+
+        let idle = db.num_idle() as u64;
+
+        usage.observe(
+            cx,
+            size.saturating_sub(idle),
+            &[
+                KeyValue::new("pool.name", POOL_NAME),
+                KeyValue::new("state", "used"),
+            ],
+        );
+
+        usage.observe(
+            cx,
+            idle,
+            &[
+                KeyValue::new("pool.name", POOL_NAME),
+                KeyValue::new("state", "idle"),
+            ],
+        );
+
+        max.observe(
+            cx,
+            u64::from(max_connections),
+            &[KeyValue::new("pool.name", POOL_NAME)],
+        );
+    })
+}
+
+/// Time taken to get a connection from the pool, as sampled by
+/// [`spawn_pool_probe`](crate::jobs::spawn_pool_probe).
+pub fn pool_wait_time(meter: &Meter) -> Histogram<f64> {
+    meter
+        .f64_histogram("db.client.connections.wait_time")
+        .with_description("Time to acquire a pool connection, sampled periodically")
+        .with_unit(Unit::new("ms"))
+        .init()
+}
+
+pub fn record_pool_wait(wait_time: &Histogram<f64>, elapsed_ms: f64) {
+    wait_time.record(
+        &Context::current(),
+        elapsed_ms,
+        &[KeyValue::new("pool.name", POOL_NAME)],
+    );
+}
@@ -0,0 +1,458 @@
+use crate::{api::webhook::WebhookDeliveries, db, telemetry, Settings};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{
+    postgres::{PgListener, PgRow},
+    Executor, PgPool, Postgres, Row,
+};
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{io::AsyncWriteExt, sync::broadcast};
+
+/// Channel notified with the id of every inserted event, once its transaction commits.
+pub const NOTIFY_CHANNEL: &str = "outbox_events";
+
+/// Longest a sink may take to publish one event.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const EVENT_COLUMNS: [Outbox; 6] = [
+    Outbox::Id,
+    Outbox::AggregateType,
+    Outbox::AggregateId,
+    Outbox::EventType,
+    Outbox::Payload,
+    Outbox::CreatedAt,
+];
+
+#[derive(Iden)]
+pub enum Outbox {
+    Table,
+    Id,
+    AggregateType,
+    AggregateId,
+    EventType,
+    Payload,
+    CreatedAt,
+    Attempts,
+    NextAttemptAt,
+    LastError,
+    DispatchedAt,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSinkKind {
+    Stdout,
+    File,
+    Webhook,
+}
+
+/// A change to a domain object, published after the transaction that made it commits.
+///
+/// Delivery is at least once and, after retries, not necessarily in order: consumers should
+/// ignore ids they've already seen and compare the payload's `version` where it has one.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DomainEvent {
+    pub id: i64,
+    pub aggregate_type: String,
+    pub aggregate_id: i32,
+    pub event_type: String,
+    pub payload: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Destination for published events. Implementations must be cheap to share across tasks.
+#[async_trait]
+pub trait EventSink: fmt::Debug + Send + Sync {
+    async fn publish(&self, event: &DomainEvent) -> anyhow::Result<()>;
+}
+
+/// Builds the sink selected by `event_sink_kind`.
+pub fn from_settings(config: &Settings) -> anyhow::Result<Arc<dyn EventSink>> {
+    let sink: Arc<dyn EventSink> = match config.event_sink_kind {
+        EventSinkKind::Stdout => Arc::new(StdoutSink),
+        EventSinkKind::File => Arc::new(FileSink::new(&config.event_sink_file_path)),
+        EventSinkKind::Webhook => Arc::new(WebhookSink::new(
+            config
+                .event_sink_webhook_url
+                .clone()
+                .context("event_sink_webhook_url is required for the webhook event sink")?,
+        )),
+    };
+
+    Ok(sink)
+}
+
+/// Prints every event as a line of JSON.
+#[derive(Debug, Clone, Copy)]
+pub struct StdoutSink;
+
+#[async_trait]
+impl EventSink for StdoutSink {
+    async fn publish(&self, event: &DomainEvent) -> anyhow::Result<()> {
+        println!("{}", serde_json::to_string(event)?);
+
+        Ok(())
+    }
+}
+
+/// Appends every event as a line of JSON to `path`.
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl EventSink for FileSink {
+    async fn publish(&self, event: &DomainEvent) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let mut line = serde_json::to_vec(event)?;
+
+        line.push(b'\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+
+        file.write_all(&line).await?;
+
+        Ok(())
+    }
+}
+
+/// POSTs every event as JSON to `url`. Anything but a 2xx answer counts as a failure.
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::builder()
+                .timeout(PUBLISH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    #[tracing::instrument(
+        name = "POST an outbox event",
+        skip(self, event),
+        fields(event_id = event.id)
+    )]
+    async fn publish(&self, event: &DomainEvent) -> anyhow::Result<()> {
+        telemetry::inject_context(self.client.post(&self.url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("x-event-id", event.id)
+            .body(serde_json::to_vec(event)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Queues an event and returns its id. Pass the transaction of the change it describes, so the
+/// event exists exactly when the change does.
+#[tracing::instrument(name = "INSERT an outbox event", skip(payload, executor))]
+pub async fn enqueue<'c, E>(
+    aggregate_type: &'static str,
+    aggregate_id: i32,
+    event_type: &'static str,
+    payload: Value,
+    executor: E,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::insert()
+        .into_table(Outbox::Table)
+        .columns([
+            Outbox::AggregateType,
+            Outbox::AggregateId,
+            Outbox::EventType,
+            Outbox::Payload,
+        ])
+        .values_panic([
+            aggregate_type.into(),
+            aggregate_id.into(),
+            event_type.into(),
+            payload.into(),
+        ])
+        .returning(Query::returning().columns([Outbox::Id]))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(executor)
+        .await
+}
+
+fn event_from_row(row: PgRow) -> DomainEvent {
+    DomainEvent {
+        id: row.get("id"),
+        aggregate_type: row.get("aggregate_type"),
+        aggregate_id: row.get("aggregate_id"),
+        event_type: row.get("event_type"),
+        payload: row.get("payload"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[tracing::instrument(name = "SELECT an outbox event", skip(db))]
+pub async fn select_event(id: i64, db: &PgPool) -> Result<Option<DomainEvent>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(EVENT_COLUMNS)
+        .from(Outbox::Table)
+        .and_where(Expr::col(Outbox::Id).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(event_from_row)
+        .fetch_optional(db)
+        .await
+}
+
+/// Owner events with an id above `after`, oldest first, limited to owner `owner_id` if given.
+#[tracing::instrument(name = "SELECT outbox events after an id", skip(db))]
+pub async fn select_owner_events_after(
+    after: i64,
+    owner_id: Option<i32>,
+    limit: u64,
+    db: &PgPool,
+) -> Result<Vec<DomainEvent>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .columns(EVENT_COLUMNS)
+        .from(Outbox::Table)
+        .and_where(Expr::col(Outbox::Id).gt(after))
+        .and_where(Expr::col(Outbox::AggregateType).eq("owner"))
+        .and_where_option(owner_id.map(|owner_id| Expr::col(Outbox::AggregateId).eq(owner_id)))
+        .order_by(Outbox::Id, Order::Asc)
+        .limit(limit)
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .map(event_from_row)
+        .fetch_all(db)
+        .await
+}
+
+/// Deletes events dispatched before `before` that no webhook delivery refers to; those stay as
+/// long as their delivery log, so they can still be redelivered. Stream clients resuming from an
+/// older id just miss the pruned ones.
+#[tracing::instrument(name = "PRUNE dispatched outbox events", skip(db))]
+pub async fn prune_dispatched(before: DateTime<Utc>, db: &PgPool) -> Result<u64, sqlx::Error> {
+    let (sql, values) = Query::delete()
+        .from_table(Outbox::Table)
+        .and_where(Expr::col(Outbox::DispatchedAt).lt(before))
+        .and_where(
+            Expr::exists(
+                Query::select()
+                    .expr(Expr::val(1))
+                    .from(WebhookDeliveries::Table)
+                    .and_where(
+                        Expr::col((WebhookDeliveries::Table, WebhookDeliveries::EventId))
+                            .equals((Outbox::Table, Outbox::Id)),
+                    )
+                    .to_owned(),
+            )
+            .not(),
+        )
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+/// Forwards committed events announced on [`NOTIFY_CHANNEL`] to `sender`, so every instance
+/// sees every event no matter which one wrote it. Only returns on error.
+///
+/// Notifications sent while the connection is down are lost; stream clients catch up by
+/// reconnecting with the last id they saw.
+pub async fn relay_notifications(
+    sender: &broadcast::Sender<Arc<DomainEvent>>,
+    db: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(db).await?;
+
+    listener.listen(NOTIFY_CHANNEL).await?;
+
+    loop {
+        let notification = listener.recv().await?;
+
+        if sender.receiver_count() == 0 {
+            continue;
+        }
+
+        let Ok(id) = notification.payload().parse() else {
+            continue;
+        };
+
+        if let Some(event) = select_event(id, db).await? {
+            // Fails only when the last subscriber left in the meantime.
+            let _ = sender.send(Arc::new(event));
+        }
+    }
+}
+
+/// Tunables for [`dispatch`], read from `Settings`.
+#[derive(Debug, Clone, Copy)]
+pub struct DispatchPolicy {
+    pub batch_size: u64,
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+}
+
+impl DispatchPolicy {
+    pub fn from_settings(config: &Settings) -> Self {
+        Self {
+            batch_size: config.outbox_batch_size,
+            retry_base: Duration::from_millis(config.outbox_retry_base_ms),
+            retry_max: Duration::from_millis(config.outbox_retry_max_ms),
+        }
+    }
+}
+
+/// Delay before retrying after the `attempts`-th failure: base · 2^(attempts - 1), capped at `max`.
+pub(crate) fn backoff(base: Duration, max: Duration, attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+
+    base.saturating_mul(1 << exponent).min(max)
+}
+
+/// Publishes one batch of due events, oldest first, and returns how many were delivered.
+///
+/// Events are locked with `SKIP LOCKED` so several instances can dispatch side by side. The
+/// batch is claimed in a short transaction by pushing its next attempt past the time publishing
+/// may take, then published without holding a connection, and the results are written in a
+/// second transaction. An event is only marked dispatched after the sink accepted it; a crash
+/// in between means it's published again once the claim lapses.
+#[tracing::instrument(name = "DISPATCH outbox events", skip(sink, db))]
+pub async fn dispatch(
+    sink: &dyn EventSink,
+    policy: DispatchPolicy,
+    db: &PgPool,
+) -> Result<usize, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let (sql, values) = Query::select()
+        .columns(EVENT_COLUMNS)
+        .column(Outbox::Attempts)
+        .from(Outbox::Table)
+        .and_where(Expr::col(Outbox::DispatchedAt).is_null())
+        .and_where(Expr::col(Outbox::NextAttemptAt).lte(Expr::current_timestamp()))
+        .order_by(Outbox::Id, Order::Asc)
+        .limit(policy.batch_size)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let events = db::query_with(&sql, values)
+        .map(|row: PgRow| {
+            let attempts: i32 = row.get("attempts");
+
+            (event_from_row(row), attempts)
+        })
+        .fetch_all(&mut tx)
+        .await?;
+
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    let claimed_until = Utc::now()
+        + chrono::Duration::from_std(PUBLISH_TIMEOUT.saturating_mul(events.len() as u32 + 1))
+            .unwrap_or_else(|_| chrono::Duration::max_value());
+
+    let (sql, values) = Query::update()
+        .table(Outbox::Table)
+        .value(Outbox::NextAttemptAt, claimed_until)
+        .and_where(Expr::col(Outbox::Id).is_in(events.iter().map(|(event, _)| event.id)))
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values).execute(&mut tx).await?;
+
+    tx.commit().await?;
+
+    let mut delivered = 0;
+
+    let mut updates = Vec::with_capacity(events.len());
+
+    for (event, attempts) in events {
+        let update = match sink.publish(&event).await {
+            Ok(()) => {
+                delivered += 1;
+
+                Query::update()
+                    .table(Outbox::Table)
+                    .value(Outbox::DispatchedAt, Expr::current_timestamp())
+                    .and_where(Expr::col(Outbox::Id).eq(event.id))
+                    .to_owned()
+            }
+            Err(error) => {
+                let attempts = attempts + 1;
+
+                let retry_at = Utc::now()
+                    + chrono::Duration::from_std(backoff(
+                        policy.retry_base,
+                        policy.retry_max,
+                        attempts,
+                    ))
+                    .unwrap_or_else(|_| chrono::Duration::zero());
+
+                tracing::warn!(
+                    event_id = event.id,
+                    attempts,
+                    %retry_at,
+                    "Failed to publish outbox event: {:?}",
+                    error
+                );
+
+                Query::update()
+                    .table(Outbox::Table)
+                    .value(Outbox::Attempts, attempts)
+                    .value(Outbox::NextAttemptAt, retry_at)
+                    .value(Outbox::LastError, format!("{:#}", error))
+                    .and_where(Expr::col(Outbox::Id).eq(event.id))
+                    .to_owned()
+            }
+        };
+
+        updates.push(update);
+    }
+
+    let mut tx = db.begin().await?;
+
+    for update in updates {
+        let (sql, values) = update.build_sqlx(PostgresQueryBuilder);
+
+        db::query_with(&sql, values).execute(&mut tx).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(delivered)
+}
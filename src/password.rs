@@ -0,0 +1,55 @@
+use anyhow::Context;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::rngs::OsRng;
+use std::sync::OnceLock;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Hashes a password with Argon2id on the blocking thread pool.
+pub async fn hash(password: String) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|error| anyhow::anyhow!("Failed to hash password: {}", error))
+    })
+    .await
+    .context("Password hashing task panicked")?
+}
+
+/// Checks a password against a stored PHC hash. Malformed hashes never verify.
+pub async fn verify(password: String, hash: String) -> anyhow::Result<bool> {
+    tokio::task::spawn_blocking(move || {
+        let Ok(parsed) = PasswordHash::new(&hash) else {
+            return false;
+        };
+
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+    .await
+    .context("Password verification task panicked")
+}
+
+/// Spends as long as [`verify`] on a login for an unknown email, so response times don't tell
+/// which addresses are registered. Never verifies.
+pub async fn verify_unknown(password: String) -> anyhow::Result<bool> {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+    let hash = match DUMMY_HASH.get() {
+        Some(hash) => hash.clone(),
+        None => {
+            let hash = self::hash(crate::token::generate()).await?;
+
+            DUMMY_HASH.get_or_init(|| hash).clone()
+        }
+    };
+
+    verify(password, hash).await.map(|_| false)
+}
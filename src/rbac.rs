@@ -0,0 +1,246 @@
+use crate::db;
+use sea_query::*;
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Executor, PgPool, Postgres, Row};
+use std::{fmt, str::FromStr};
+
+#[derive(Iden)]
+pub enum Roles {
+    Table,
+    Id,
+    Name,
+}
+
+#[derive(Iden)]
+pub enum OwnerRoles {
+    Table,
+    OwnerId,
+    RoleId,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Support,
+    Owner,
+}
+
+/// Actions on resources that don't belong to the caller. Owners may always act on themselves.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    ReadOwners,
+    UpdateOwners,
+    DeleteOwners,
+    UnlockLogins,
+    ManageRoles,
+    ReadAuditLog,
+    ManageLogging,
+}
+
+/// What an API key may do on behalf of its owner. Keys never carry their owner's staff roles.
+///
+/// `business:*` scopes are reserved for the business listing endpoints and grant nothing yet.
+/// `webhook:*` scopes cover the owner's webhook subscriptions and their delivery log.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    #[serde(rename = "owner:read")]
+    OwnerRead,
+    #[serde(rename = "owner:write")]
+    OwnerWrite,
+    #[serde(rename = "business:read")]
+    BusinessRead,
+    #[serde(rename = "business:write")]
+    BusinessWrite,
+    #[serde(rename = "webhook:read")]
+    WebhookRead,
+    #[serde(rename = "webhook:write")]
+    WebhookWrite,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Support => "support",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Role::Admin => &[
+                Permission::ReadOwners,
+                Permission::UpdateOwners,
+                Permission::DeleteOwners,
+                Permission::UnlockLogins,
+                Permission::ManageRoles,
+                Permission::ReadAuditLog,
+                Permission::ManageLogging,
+            ],
+            Role::Support => &[
+                Permission::ReadOwners,
+                Permission::UpdateOwners,
+                Permission::UnlockLogins,
+            ],
+            Role::Owner => &[],
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Role::Admin => 2,
+            Role::Support => 1,
+            Role::Owner => 0,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(Role::Admin),
+            "support" => Ok(Role::Support),
+            "owner" => Ok(Role::Owner),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::OwnerRead => "owner:read",
+            Scope::OwnerWrite => "owner:write",
+            Scope::BusinessRead => "business:read",
+            Scope::BusinessWrite => "business:write",
+            Scope::WebhookRead => "webhook:read",
+            Scope::WebhookWrite => "webhook:write",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner:read" => Ok(Scope::OwnerRead),
+            "owner:write" => Ok(Scope::OwnerWrite),
+            "business:read" => Ok(Scope::BusinessRead),
+            "business:write" => Ok(Scope::BusinessWrite),
+            "webhook:read" => Ok(Scope::WebhookRead),
+            "webhook:write" => Ok(Scope::WebhookWrite),
+            other => Err(format!("Unknown scope: {}", other)),
+        }
+    }
+}
+
+/// Whether the highest of `roles` ranks above the highest of `others`, e.g. an admin above
+/// support staff. Owners without any role rank like [`Role::Owner`].
+pub fn outranks(roles: &[Role], others: &[Role]) -> bool {
+    let highest = |roles: &[Role]| roles.iter().map(Role::rank).max().unwrap_or_default();
+
+    highest(roles) > highest(others)
+}
+
+/// Whether any of `roles` grants `permission`.
+pub fn has_permission(roles: &[Role], permission: Permission) -> bool {
+    roles
+        .iter()
+        .any(|role| role.permissions().contains(&permission))
+}
+
+#[tracing::instrument(name = "SELECT an owner's roles", skip(db))]
+pub async fn select_owner_roles(owner_id: i32, db: &PgPool) -> Result<Vec<Role>, sqlx::Error> {
+    let (sql, values) = Query::select()
+        .column((Roles::Table, Roles::Name))
+        .from(OwnerRoles::Table)
+        .inner_join(
+            Roles::Table,
+            Expr::col((Roles::Table, Roles::Id)).equals((OwnerRoles::Table, OwnerRoles::RoleId)),
+        )
+        .and_where(Expr::col((OwnerRoles::Table, OwnerRoles::OwnerId)).eq(owner_id))
+        .order_by((Roles::Table, Roles::Name), Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let names = db::query_with(&sql, values)
+        .map(|row: PgRow| row.get::<String, _>("name"))
+        .fetch_all(db)
+        .await?;
+
+    // Roles unknown to this build are ignored rather than failing authentication.
+    Ok(names.iter().filter_map(|name| name.parse().ok()).collect())
+}
+
+/// Grants `role` to an owner. Granting a role twice is a no-op.
+#[tracing::instrument(name = "GRANT a role", skip(executor))]
+pub async fn grant_role<'c, E>(owner_id: i32, role: Role, executor: E) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::insert()
+        .into_table(OwnerRoles::Table)
+        .columns([OwnerRoles::OwnerId, OwnerRoles::RoleId])
+        .select_from(
+            Query::select()
+                .expr(Expr::val(owner_id))
+                .column(Roles::Id)
+                .from(Roles::Table)
+                .and_where(Expr::col(Roles::Name).eq(role.as_str()))
+                .to_owned(),
+        )
+        .map_err(|error| sqlx::Error::Protocol(error.to_string()))?
+        .on_conflict(
+            OnConflict::columns([OwnerRoles::OwnerId, OwnerRoles::RoleId])
+                .do_nothing()
+                .to_owned(),
+        )
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+#[tracing::instrument(name = "REVOKE a role", skip(executor))]
+pub async fn revoke_role<'c, E>(owner_id: i32, role: Role, executor: E) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sql, values) = Query::delete()
+        .from_table(OwnerRoles::Table)
+        .and_where(Expr::col(OwnerRoles::OwnerId).eq(owner_id))
+        .and_where(
+            Expr::col(OwnerRoles::RoleId).in_subquery(
+                Query::select()
+                    .column(Roles::Id)
+                    .from(Roles::Table)
+                    .and_where(Expr::col(Roles::Name).eq(role.as_str()))
+                    .to_owned(),
+            ),
+        )
+        .build_sqlx(PostgresQueryBuilder);
+
+    db::query_with(&sql, values)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+}
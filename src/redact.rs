@@ -0,0 +1,207 @@
+use crate::Settings;
+use http::{HeaderMap, Uri};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+pub const MASK: &str = "[REDACTED]";
+
+/// Headers carrying credentials. They're masked whatever the configured lists say.
+const CREDENTIAL_HEADERS: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// Settings holding credentials: the database password is part of `database_url` and exporter
+/// API keys travel in `otel_headers`.
+const CREDENTIAL_SETTINGS: [&str; 3] = ["database_url", "otel_headers", "smtp_password"];
+
+fn lowercase(names: &[impl AsRef<str>]) -> HashSet<String> {
+    names
+        .iter()
+        .map(|name| name.as_ref().to_ascii_lowercase())
+        .collect()
+}
+
+static POLICY: RwLock<Option<Arc<RedactionPolicy>>> = RwLock::new(None);
+
+/// What telemetry may record of requests and payloads.
+///
+/// Header values are shown when the header is on the allowlist (or the allowlist is empty)
+/// and not on the denylist. Fields are masked by name wherever they appear: in payload
+/// `Debug` output, nested objects included, and in query strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactionPolicy {
+    allowed_headers: HashSet<String>,
+    denied_headers: HashSet<String>,
+    fields: HashSet<String>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        let none: [&str; 0] = [];
+
+        Self::new(
+            &none,
+            &none,
+            &[
+                "password",
+                "current_password",
+                "new_password",
+                "token",
+                "secret",
+                "key",
+            ],
+        )
+    }
+}
+
+impl RedactionPolicy {
+    pub fn new(
+        allowed_headers: &[impl AsRef<str>],
+        denied_headers: &[impl AsRef<str>],
+        fields: &[impl AsRef<str>],
+    ) -> Self {
+        Self {
+            allowed_headers: lowercase(allowed_headers),
+            denied_headers: lowercase(denied_headers)
+                .into_iter()
+                .chain(CREDENTIAL_HEADERS.map(String::from))
+                .collect(),
+            fields: lowercase(fields),
+        }
+    }
+
+    pub fn from_settings(config: &Settings) -> Self {
+        Self::new(
+            &config.redact_headers_allowlist,
+            &config.redact_headers_denylist,
+            &config.redact_fields,
+        )
+    }
+
+    pub fn shows_header(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+
+        (self.allowed_headers.is_empty() || self.allowed_headers.contains(&name))
+            && !self.denied_headers.contains(&name)
+    }
+
+    pub fn masks_field(&self, name: &str) -> bool {
+        self.fields.contains(&name.to_ascii_lowercase())
+    }
+
+    /// `headers` for recording with `?`, masked values included so it's visible they were sent.
+    pub fn headers<'a>(&'a self, headers: &'a HeaderMap) -> RedactedHeaders<'a> {
+        RedactedHeaders {
+            headers,
+            policy: self,
+        }
+    }
+
+    /// `uri` with the values of masked query parameters replaced.
+    pub fn uri(&self, uri: &Uri) -> String {
+        let Some(query) = uri.query() else {
+            return uri.to_string();
+        };
+
+        let query: Vec<String> = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.masks_field(name) => format!("{}={}", name, MASK),
+                _ => pair.to_string(),
+            })
+            .collect();
+
+        format!("{}?{}", uri.path(), query.join("&"))
+    }
+
+    /// Replaces masked fields anywhere in `value`.
+    pub fn mask(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (name, value) in fields.iter_mut() {
+                    if self.masks_field(name) && !value.is_null() {
+                        *value = Value::from(MASK);
+                    } else {
+                        self.mask(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.mask(value)),
+            _ => {}
+        }
+    }
+}
+
+pub struct RedactedHeaders<'a> {
+    headers: &'a HeaderMap,
+    policy: &'a RedactionPolicy,
+}
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+
+        for (name, value) in self.headers {
+            if self.policy.shows_header(name.as_str()) {
+                map.entry(&name.as_str(), value);
+            } else {
+                map.entry(&name.as_str(), &MASK);
+            }
+        }
+
+        map.finish()
+    }
+}
+
+/// Makes `policy` the one used for payload `Debug` output in this process.
+pub fn install(policy: Arc<RedactionPolicy>) {
+    *POLICY.write().unwrap() = Some(policy);
+}
+
+/// The installed policy, or the default one when none was installed.
+pub fn policy() -> Arc<RedactionPolicy> {
+    POLICY.read().unwrap().clone().unwrap_or_default()
+}
+
+/// `Debug` for payloads holding secrets: prints `name` followed by the value as JSON, with the
+/// policy's fields masked. Use it instead of deriving `Debug`, so `#[tracing::instrument]`
+/// and error messages can't leak them.
+pub fn debug<T: Serialize>(value: &T, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut value = serde_json::to_value(value).map_err(|_| fmt::Error)?;
+
+    policy().mask(&mut value);
+
+    write!(f, "{} {}", name, value)
+}
+
+/// `Debug` for `Settings`: like [`debug`], with the credential settings masked as well.
+pub fn settings(config: &Settings, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut value = serde_json::to_value(config).map_err(|_| fmt::Error)?;
+
+    policy().mask(&mut value);
+
+    if let Value::Object(fields) = &mut value {
+        for name in CREDENTIAL_SETTINGS {
+            let set = match name {
+                "smtp_password" => config.smtp_password.is_some(),
+                _ => fields.get(name).is_some_and(|value| !value.is_null()),
+            };
+
+            fields.insert(
+                name.to_string(),
+                if set { Value::from(MASK) } else { Value::Null },
+            );
+        }
+    }
+
+    write!(f, "Settings {}", value)
+}
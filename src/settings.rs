@@ -1,21 +1,86 @@
+use crate::{
+    logging::{LogFormat, LogRotation},
+    mailer::MailerKind,
+    outbox::EventSinkKind,
+    redact,
+    telemetry::{MetricsExporter, TelemetryExporter},
+};
 use config::{Config, ConfigError, Environment, File};
 use dotenvy::dotenv;
-use serde_derive::Deserialize;
-use std::env;
+use secrecy::Secret;
+use serde_derive::{Deserialize, Serialize};
+use std::{env, fmt};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[allow(unused)]
 pub struct Settings {
     pub host: String,
     pub port: u16,
     pub database_url: String,
     pub db_max_connections: u32,
-    pub honeycomb_api_key: String,
-    pub honeycomb_dataset: String,
-    pub honeycomb_host: String,
-    pub honeycomb_port: String,
-    pub otel_collector_host: String,
-    pub otel_collector_port: String,
+    pub health_check_timeout_ms: u64,
+    pub db_pool_probe_interval_secs: u64,
+    pub db_slow_query_threshold_ms: u64,
+    pub telemetry_exporter: TelemetryExporter,
+    pub otel_endpoint: String,
+    pub otel_headers: Vec<String>,
+    pub otel_timeout_ms: u64,
+    pub otel_sampling_ratio: f64,
+    pub otel_service_name: String,
+    pub otel_resource_attributes: Vec<String>,
+    pub metrics_exporter: MetricsExporter,
+    pub log_format: LogFormat,
+    pub log_level: String,
+    pub log_file_dir: Option<String>,
+    pub log_file_rotation: LogRotation,
+    pub log_file_prefix: String,
+    pub owner_retention_days: i64,
+    pub owner_purge_interval_secs: u64,
+    pub public_base_url: String,
+    pub email_verification_ttl_minutes: i64,
+    pub password_reset_ttl_minutes: i64,
+    pub session_ttl_minutes: i64,
+    pub idempotency_key_ttl_hours: i64,
+    pub idempotency_key_lease_secs: i64,
+    pub client_ip_header: Option<String>,
+    pub login_max_account_failures: u32,
+    pub login_max_ip_failures: u32,
+    pub login_backoff_base_ms: u64,
+    pub login_backoff_max_ms: u64,
+    pub login_lockout_minutes: u64,
+    pub mailer_kind: MailerKind,
+    pub mailer_from: String,
+    pub mailer_file_dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    #[serde(skip_serializing)]
+    pub smtp_password: Option<Secret<String>>,
+    pub event_sink_kind: EventSinkKind,
+    pub event_sink_file_path: String,
+    pub event_sink_webhook_url: Option<String>,
+    pub outbox_dispatch_interval_ms: u64,
+    pub outbox_batch_size: u64,
+    pub outbox_retry_base_ms: u64,
+    pub outbox_retry_max_ms: u64,
+    pub outbox_retention_days: i64,
+    pub event_stream_auth_check_secs: u64,
+    pub webhook_dispatch_interval_ms: u64,
+    pub webhook_batch_size: u64,
+    pub webhook_retry_base_ms: u64,
+    pub webhook_retry_max_ms: u64,
+    pub webhook_max_attempts: i32,
+    pub webhook_timeout_ms: u64,
+    pub webhook_allow_private_targets: bool,
+    pub redact_headers_allowlist: Vec<String>,
+    pub redact_headers_denylist: Vec<String>,
+    pub redact_fields: Vec<String>,
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redact::settings(self, f)
+    }
 }
 
 impl Settings {
@@ -33,7 +98,17 @@ impl Settings {
             // Load from "env" based configs.
             .add_source(File::with_name(&format!("config/{}", run_mode)).required(false))
             // Load from ENVIRONMENT
-            .add_source(Environment::with_prefix("APP"))
+            // Lists are comma separated, e.g. APP_OTEL_HEADERS="x-honeycomb-team=...,x-tenant=...".
+            .add_source(
+                Environment::with_prefix("APP")
+                    .try_parsing(true)
+                    .list_separator(",")
+                    .with_list_parse_key("otel_headers")
+                    .with_list_parse_key("otel_resource_attributes")
+                    .with_list_parse_key("redact_headers_allowlist")
+                    .with_list_parse_key("redact_headers_denylist")
+                    .with_list_parse_key("redact_fields"),
+            )
             .build()?;
 
         // Deserialize and freeze configs as is.
@@ -1,24 +1,45 @@
-use http::{HeaderName, HeaderValue, Request, Response};
-use std::{fmt, time::Duration};
+use chrono::{DateTime, Utc};
+use http::{HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode};
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
 use tower_http::{
+    classify::ServerErrorsFailureClass,
     request_id::{MakeRequestId, RequestId},
     trace::{MakeSpan, OnFailure, OnResponse},
 };
 use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use uuid::Uuid;
 
-use crate::Settings;
+use crate::{logging, metrics, redact::RedactionPolicy, Settings};
 use opentelemetry::{
-    global::shutdown_tracer_provider,
+    metrics::{Counter, Meter},
+    propagation::{Extractor, Injector, TextMapPropagator},
+    runtime,
     sdk::{
-        trace::{self, RandomIdGenerator},
+        export::{
+            metrics::{aggregation::cumulative_temporality_selector, stdout as metrics_stdout},
+            trace::stdout,
+        },
+        metrics::{controllers, controllers::BasicController, processors, selectors},
+        propagation::TraceContextPropagator,
+        trace::{self, RandomIdGenerator, Sampler, Tracer},
         Resource,
     },
-    trace::TraceError,
-    KeyValue,
+    trace::TraceContextExt,
+    Context, KeyValue,
+};
+use opentelemetry_otlp::{HttpExporterBuilder, TonicExporterBuilder, WithExportConfig};
+use opentelemetry_prometheus::PrometheusExporter;
+use serde::{Deserialize, Serialize};
+use tonic::{
+    metadata::{MetadataKey, MetadataMap},
+    transport::ClientTlsConfig,
 };
-use opentelemetry_otlp::WithExportConfig;
-use tonic::{metadata::MetadataMap, transport::ClientTlsConfig};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{layer::SubscriberExt, Layer, Registry};
 use url::Url;
 
 #[derive(Debug, Clone)]
@@ -41,25 +62,104 @@ impl<B> OnResponse<B> for OnResponseTrace {
     }
 }
 
+/// Kind of failure a request ended in, as reported in logs, spans and the failure metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The handler answered with a 5xx status.
+    ServerError,
+    /// The service itself failed, e.g. the response body errored mid-stream.
+    ServiceError,
+    /// Something gave up waiting: a 504 answer or an error saying it timed out.
+    Timeout,
+}
+
+impl FailureKind {
+    pub fn classify(failure: &ServerErrorsFailureClass) -> Self {
+        match failure {
+            ServerErrorsFailureClass::StatusCode(StatusCode::GATEWAY_TIMEOUT) => Self::Timeout,
+            ServerErrorsFailureClass::StatusCode(_) => Self::ServerError,
+            ServerErrorsFailureClass::Error(error)
+                if error.to_lowercase().contains("timed out") =>
+            {
+                Self::Timeout
+            }
+            ServerErrorsFailureClass::Error(_) => Self::ServiceError,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ServerError => "server_error",
+            Self::ServiceError => "service_error",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+/// Marks the request span as failed: OpenTelemetry error status plus `http.failure_kind`, and
+/// `exception.message` when an error rather than a status code failed the request. Also logs
+/// an error inside the span so it carries the request id, and counts the failure in
+/// `http.server.failures` by kind.
 #[derive(Debug, Clone)]
-pub struct OnFailureTrace;
+pub struct OnFailureTrace {
+    failures: Counter<u64>,
+}
+
+impl OnFailureTrace {
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            failures: meter
+                .u64_counter("http.server.failures")
+                .with_description("Requests that failed, by kind")
+                .init(),
+        }
+    }
+}
+
+impl OnFailure<ServerErrorsFailureClass> for OnFailureTrace {
+    fn on_failure(&mut self, failure: ServerErrorsFailureClass, latency: Duration, span: &Span) {
+        let kind = FailureKind::classify(&failure);
+
+        let message = match &failure {
+            ServerErrorsFailureClass::StatusCode(status) => status.to_string(),
+            ServerErrorsFailureClass::Error(error) => error.clone(),
+        };
+
+        span.record("otel.status_code", "ERROR");
+
+        span.record("otel.status_message", message.as_str());
 
-impl<E> OnFailure<E> for OnFailureTrace
-where
-    E: fmt::Display,
-{
-    fn on_failure(&mut self, _error: E, _latency: Duration, _span: &Span) {
-        tracing::debug!("something went wrong")
+        span.record("http.failure_kind", kind.as_str());
+
+        if let ServerErrorsFailureClass::Error(error) = &failure {
+            span.record("exception.message", error.as_str());
+        }
+
+        tracing::error!(
+            parent: span,
+            failure = kind.as_str(),
+            latency = format_args!("{} μs", latency.as_micros()),
+            "request failed: {}",
+            message
+        );
+
+        self.failures.add(
+            &span.context(),
+            1,
+            &[KeyValue::new("failure.class", kind.as_str())],
+        );
     }
 }
 
 //https://betterprogramming.pub/production-grade-logging-in-rust-applications-2c7fffd108a6
 #[derive(Debug, Clone)]
-pub struct InitialSpan {}
+pub struct InitialSpan {
+    policy: Arc<RedactionPolicy>,
+}
 
 impl InitialSpan {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(policy: Arc<RedactionPolicy>) -> Self {
+        Self { policy }
     }
 }
 
@@ -67,22 +167,72 @@ impl<B> MakeSpan<B> for InitialSpan {
     fn make_span(&mut self, request: &Request<B>) -> Span {
         let request_id = request
             .headers()
-            .get(&HeaderName::from_static("x-request-id"))
+            .get(HeaderName::from_static("x-request-id"))
             .unwrap();
 
-        tracing::span!(
+        let span = tracing::span!(
             Level::INFO,
             "http-request",
             status_code = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+            "http.failure_kind" = tracing::field::Empty,
+            "exception.message" = tracing::field::Empty,
             method = ?request.method(),
-            uri = ?request.uri(),
+            uri = %self.policy.uri(request.uri()),
             version = ?request.version(),
-            headers = ?request.headers(),
+            headers = ?self.policy.headers(request.headers()),
             request_id = ?request_id,
-        )
+        );
+
+        let parent = extract_context(request.headers());
+
+        if parent.span().span_context().is_valid() {
+            span.set_parent(parent);
+        }
+
+        span
+    }
+}
+
+/// Longest `x-request-id` accepted from clients.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+fn is_valid_request_id(value: &HeaderValue) -> bool {
+    let value = value.as_bytes();
+
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .iter()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.:/+=".contains(byte))
+}
+
+/// Drops an `x-request-id` we won't take from the client, so [`HTTPRequestId`] replaces it.
+///
+/// Ids set by gateways in front of us are kept, provided there's exactly one and it's a short
+/// token: they end up in every log line and response, so arbitrary input isn't echoed there.
+pub fn discard_invalid_request_id<B>(mut request: Request<B>) -> Request<B> {
+    let x_request_id = HeaderName::from_static("x-request-id");
+
+    let mut values = request.headers().get_all(&x_request_id).iter();
+
+    let valid = match (values.next(), values.next()) {
+        (None, _) => true,
+        (Some(value), None) => is_valid_request_id(value),
+        _ => false,
+    };
+
+    if !valid {
+        tracing::debug!("Discarding invalid x-request-id header");
+
+        request.headers_mut().remove(&x_request_id);
     }
+
+    request
 }
 
+/// Generates ids for requests arriving without a valid one.
 #[derive(Clone, Default)]
 pub struct HTTPRequestId {}
 
@@ -96,45 +246,349 @@ impl MakeRequestId for HTTPRequestId {
     }
 }
 
-fn init_tracer(config: &Settings) -> Result<opentelemetry::sdk::trace::Tracer, TraceError> {
-    let mut metadata = MetadataMap::with_capacity(2);
+struct HeaderExtractor<'a>(&'a HeaderMap);
 
-    metadata.insert(
-        "x-honeycomb-team",
-        config.honeycomb_api_key.parse().unwrap(),
-    );
-    metadata.insert(
-        "x-honeycomb-dataset",
-        config.honeycomb_dataset.parse().unwrap(),
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// The W3C trace context (`traceparent` and `tracestate`) sent by the caller. Empty when there
+/// is none or it doesn't parse.
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Adds the current span's W3C trace context to an outgoing request, so whatever the receiver
+/// records joins our trace.
+pub fn inject_context(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let mut headers = HeaderMap::new();
+
+    TraceContextPropagator::new().inject_context(
+        &Span::current().context(),
+        &mut HeaderInjector(&mut headers),
     );
 
-    let host_and_port = format!("{}:{}", config.honeycomb_host, config.honeycomb_port);
-
-    let endpoint = Url::parse(&host_and_port).expect("endpoint is not a valid url");
-
-    opentelemetry_otlp::new_pipeline()
-        .tracing()
-        .with_exporter(
-            opentelemetry_otlp::new_exporter()
-                .tonic()
-                .with_endpoint(endpoint.as_str())
-                .with_metadata(metadata)
-                .with_tls_config(
-                    ClientTlsConfig::new().domain_name(
-                        endpoint
-                            .host_str()
-                            .expect("the specified endpoint should have a valid host"),
-                    ),
-                )
-                .with_timeout(std::time::Duration::from_secs(2)),
-        )
-        .with_trace_config(
-            trace::config()
-                .with_id_generator(RandomIdGenerator::default())
-                .with_resource(Resource::new(vec![KeyValue::new(
-                    "service.name",
-                    "proximity-service",
-                )])),
+    request.headers(headers)
+}
+
+/// State of the OpenTelemetry exporters as seen by the readiness probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExporterState {
+    /// No exporter was installed, e.g. in tests.
+    Disabled,
+    Healthy,
+    Failing {
+        error: String,
+        at: DateTime<Utc>,
+    },
+}
+
+static EXPORTER_STATE: Mutex<Option<ExporterState>> = Mutex::new(None);
+
+/// Minutes after its last error that an exporter still counts as failing.
+const EXPORTER_ERROR_WINDOW_MINUTES: i64 = 5;
+
+/// Routes OpenTelemetry export errors to stderr and remembers the latest one for
+/// [`exporter_state`]. Called once the exporters are installed.
+fn watch_exporters() -> Result<(), opentelemetry::global::Error> {
+    *EXPORTER_STATE.lock().unwrap() = Some(ExporterState::Healthy);
+
+    opentelemetry::global::set_error_handler(|error| {
+        // Not through `tracing`: its OpenTelemetry layer could fail the same way again.
+        eprintln!("OpenTelemetry error: {}", error);
+
+        *EXPORTER_STATE.lock().unwrap() = Some(ExporterState::Failing {
+            error: error.to_string(),
+            at: Utc::now(),
+        });
+    })
+}
+
+pub fn exporter_state() -> ExporterState {
+    match EXPORTER_STATE.lock().unwrap().clone() {
+        None => ExporterState::Disabled,
+        Some(ExporterState::Failing { at, .. })
+            if Utc::now() - at > chrono::Duration::minutes(EXPORTER_ERROR_WINDOW_MINUTES) =>
+        {
+            ExporterState::Healthy
+        }
+        Some(state) => state,
+    }
+}
+
+/// Where traces and metrics go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryExporter {
+    /// Nothing is exported, logs are still written.
+    None,
+    /// Spans and metrics are printed to stdout, for local debugging.
+    Stdout,
+    /// OTLP over gRPC, e.g. to a collector on port 4317.
+    OtlpGrpc,
+    /// OTLP over HTTP with protobuf bodies, e.g. to a collector on port 4318.
+    OtlpHttp,
+}
+
+/// How metrics leave the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsExporter {
+    /// Pushed through the `telemetry_exporter` pipeline, along with traces.
+    Telemetry,
+    /// Scraped from `GET /metrics` in the Prometheus text format.
+    Prometheus,
+}
+
+static PROMETHEUS: OnceLock<PrometheusExporter> = OnceLock::new();
+
+/// The exporter behind `GET /metrics`, once [`init`] installed it.
+pub fn prometheus_exporter() -> Option<&'static PrometheusExporter> {
+    PROMETHEUS.get()
+}
+
+/// Keeps the exporters running. Dropping it flushes what's buffered and shuts them down, so
+/// hold it until the server has stopped.
+#[must_use = "telemetry shuts down when the guard is dropped"]
+pub struct TelemetryGuard {
+    metrics: Option<BasicController>,
+    tracing: bool,
+    _log_file: Option<WorkerGuard>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(controller) = self.metrics.take() {
+            if let Err(error) = controller.stop(&Context::current()) {
+                eprintln!("Failed to stop metrics export: {}", error);
+            }
+        }
+
+        if self.tracing {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Parses `name=value` pairs, as used for exporter headers and resource attributes.
+fn pairs(entries: &[String], what: &str) -> anyhow::Result<Vec<(String, String)>> {
+    entries
+        .iter()
+        .map(|entry| {
+            entry
+                .split_once('=')
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| anyhow::anyhow!("Invalid {} {:?}, expected name=value", what, entry))
+        })
+        .collect()
+}
+
+fn resource(config: &Settings) -> anyhow::Result<Resource> {
+    let mut attributes = vec![
+        KeyValue::new("service.name", config.otel_service_name.clone()),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ];
+
+    for (name, value) in pairs(&config.otel_resource_attributes, "resource attribute")? {
+        attributes.push(KeyValue::new(name, value));
+    }
+
+    Ok(Resource::new(attributes))
+}
+
+/// gRPC exporter for `otel_endpoint`, using TLS when its scheme is `https`.
+fn grpc_exporter(config: &Settings) -> anyhow::Result<TonicExporterBuilder> {
+    let endpoint = Url::parse(&config.otel_endpoint)?;
+
+    let mut metadata = MetadataMap::new();
+
+    for (name, value) in pairs(&config.otel_headers, "exporter header")? {
+        metadata.insert(MetadataKey::from_bytes(name.as_bytes())?, value.parse()?);
+    }
+
+    let mut exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(endpoint.as_str())
+        .with_metadata(metadata)
+        .with_timeout(Duration::from_millis(config.otel_timeout_ms));
+
+    if endpoint.scheme() == "https" {
+        let host = endpoint
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("otel_endpoint has no host"))?;
+
+        exporter = exporter.with_tls_config(ClientTlsConfig::new().domain_name(host));
+    }
+
+    Ok(exporter)
+}
+
+/// HTTP exporter posting traces to `{otel_endpoint}/v1/traces`. TLS follows the scheme.
+fn http_exporter(config: &Settings) -> anyhow::Result<HttpExporterBuilder> {
+    let endpoint = format!("{}/v1/traces", config.otel_endpoint.trim_end_matches('/'));
+
+    Ok(opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(endpoint)
+        .with_headers(
+            pairs(&config.otel_headers, "exporter header")?
+                .into_iter()
+                .collect(),
         )
-        .install_batch(opentelemetry::runtime::TokioCurrentThread)
+        .with_timeout(Duration::from_millis(config.otel_timeout_ms)))
+}
+
+fn init_tracer(config: &Settings, resource: Resource) -> anyhow::Result<Option<Tracer>> {
+    let trace_config = trace::config()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.otel_sampling_ratio,
+        ))))
+        .with_id_generator(RandomIdGenerator::default())
+        .with_resource(resource);
+
+    let tracer = match config.telemetry_exporter {
+        TelemetryExporter::None => return Ok(None),
+        TelemetryExporter::Stdout => stdout::new_pipeline()
+            .with_trace_config(trace_config)
+            .install_simple(),
+        TelemetryExporter::OtlpGrpc => opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(grpc_exporter(config)?)
+            .with_trace_config(trace_config)
+            .install_batch(runtime::TokioCurrentThread)?,
+        TelemetryExporter::OtlpHttp => opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(http_exporter(config)?)
+            .with_trace_config(trace_config)
+            .install_batch(runtime::TokioCurrentThread)?,
+    };
+
+    Ok(Some(tracer))
+}
+
+/// Collects metrics on every scrape rather than on a timer, so nothing needs starting.
+fn init_prometheus(resource: Resource) -> anyhow::Result<()> {
+    let controller = controllers::basic(processors::factory(
+        selectors::simple::histogram(metrics::DURATION_BUCKETS_MS),
+        cumulative_temporality_selector(),
+    ))
+    .with_resource(resource)
+    .build();
+
+    let exporter = opentelemetry_prometheus::exporter(controller).try_init()?;
+
+    PROMETHEUS
+        .set(exporter)
+        .map_err(|_| anyhow::anyhow!("The Prometheus exporter is already installed"))
+}
+
+/// Starts exporting metrics and installs the global meter provider. Instruments are the same
+/// whichever exporter is used. The OTLP exporter this build uses only sends metrics over gRPC.
+fn init_metrics(config: &Settings, resource: Resource) -> anyhow::Result<Option<BasicController>> {
+    if config.metrics_exporter == MetricsExporter::Prometheus {
+        init_prometheus(resource)?;
+
+        return Ok(None);
+    }
+
+    let controller = match config.telemetry_exporter {
+        TelemetryExporter::None | TelemetryExporter::OtlpHttp => return Ok(None),
+        TelemetryExporter::Stdout => {
+            let controller = controllers::basic(processors::factory(
+                selectors::simple::histogram(metrics::DURATION_BUCKETS_MS),
+                cumulative_temporality_selector(),
+            ))
+            .with_exporter(metrics_stdout().build()?)
+            .with_resource(resource)
+            .build();
+
+            controller.start(&Context::current(), runtime::TokioCurrentThread)?;
+
+            opentelemetry::global::set_meter_provider(controller.clone());
+
+            controller
+        }
+        TelemetryExporter::OtlpGrpc => opentelemetry_otlp::new_pipeline()
+            .metrics(
+                selectors::simple::histogram(metrics::DURATION_BUCKETS_MS),
+                cumulative_temporality_selector(),
+                runtime::TokioCurrentThread,
+            )
+            .with_exporter(grpc_exporter(config)?)
+            .with_resource(resource)
+            .build()?,
+    };
+
+    Ok(Some(controller))
+}
+
+/// Sets up tracing, metrics and logging for the process as configured.
+///
+/// Spans reach the exporter through the `tracing` OpenTelemetry layer, sampled by
+/// `otel_sampling_ratio` unless the caller's trace context already decided. Logs are written
+/// as `log_format` to stdout and, if configured, a rotated file. Call once, from within the
+/// Tokio runtime. Fails for `otlp_http` unless metrics go to Prometheus, as nothing would
+/// export them.
+pub fn init(config: &Settings) -> anyhow::Result<TelemetryGuard> {
+    if config.telemetry_exporter == TelemetryExporter::OtlpHttp
+        && config.metrics_exporter == MetricsExporter::Telemetry
+    {
+        anyhow::bail!(
+            "Metrics can't be exported over OTLP HTTP, use otlp_grpc or the prometheus metrics exporter"
+        );
+    }
+
+    let resource = resource(config)?;
+
+    let tracer = init_tracer(config, resource.clone())?;
+
+    let metrics = init_metrics(config, resource)?;
+
+    let (mut layers, log_file) = logging::layers(config)?;
+
+    let (filter, log_level) = logging::reloadable_filter(config)?;
+
+    if let Some(tracer) = &tracer {
+        layers.insert(
+            0,
+            tracing_opentelemetry::layer()
+                .with_tracer(tracer.clone())
+                .boxed(),
+        );
+    }
+
+    let subscriber = Registry::default().with(layers).with(filter);
+
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    logging::install(log_level)?;
+
+    if config.telemetry_exporter != TelemetryExporter::None {
+        watch_exporters()?;
+    }
+
+    Ok(TelemetryGuard {
+        metrics,
+        tracing: tracer.is_some(),
+        _log_file: log_file,
+    })
 }
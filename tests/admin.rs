@@ -0,0 +1,260 @@
+use proximity_service::{Owners, Role};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_restore_owner(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let client = reqwest::Client::new();
+
+        client
+            .delete(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        let response = client
+            .post(format!("{}/admin/owner/{}/restore", &address, owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn test_restore_owner_requires_admin(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id = utils::test_setup(&db).await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/admin/owner/{}/restore", &address, owner_id))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .post(format!("{}/admin/owner/{}/restore", &address, owner_id))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    async fn test_restore_owner_not_deleted(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id = utils::test_setup(&db).await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/admin/owner/{}/restore", &address, owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_list_owners(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id = utils::test_setup(&db).await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let client = reqwest::Client::new();
+
+        sqlx::query(r#"update "owners" set deleted_at = now() where id = $1"#)
+            .bind(owner_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let owners: Vec<Owners> = client
+            .get(format!("{}/admin/owner", &address))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert!(owners.iter().all(|owner| owner.id != owner_id));
+
+        let owners: Vec<Owners> = client
+            .get(format!("{}/admin/owner?include_deleted=true", &address))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert!(owners.iter().any(|owner| owner.id == owner_id));
+    }
+
+    #[sqlx::test]
+    async fn test_owner_cannot_use_admin_routes(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/admin/owner", &address))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .put(format!("{}/admin/owner/{}/roles/admin", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    async fn test_grant_and_revoke_role(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!(
+                "{}/admin/owner/{}/roles/support",
+                &address, owner_id
+            ))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = client
+            .get(format!("{}/admin/owner", &address))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .delete(format!(
+                "{}/admin/owner/{}/roles/support",
+                &address, owner_id
+            ))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = client
+            .get(format!("{}/admin/owner", &address))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .put(format!(
+                "{}/admin/owner/{}/roles/patriot",
+                &address, owner_id
+            ))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert!(response.status().is_client_error());
+
+        let changes: Vec<(String, serde_json::Value)> = sqlx::query_as(
+            "select action, diff from audit_events where target_id = $1 and action like 'role_%' order by id",
+        )
+        .bind(owner_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    String::from("role_granted"),
+                    serde_json::json!({ "role": { "before": null, "after": "support" } })
+                ),
+                (
+                    String::from("role_revoked"),
+                    serde_json::json!({ "role": { "before": "support", "after": null } })
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use proximity_service::{ApiKey, ApiPayload, CreateApiKey, CreateApiKeyResponse, Role, Scope};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn new_key(scopes: Vec<Scope>, allowed_ips: Vec<&str>) -> ApiPayload<CreateApiKey> {
+        ApiPayload {
+            payload: CreateApiKey {
+                name: String::from("Partner sync"),
+                scopes,
+                expires_at: None,
+                allowed_ips: allowed_ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            },
+        }
+    }
+
+    async fn create_key(
+        address: &str,
+        owner_id: i32,
+        session: &str,
+        key: &ApiPayload<CreateApiKey>,
+    ) -> CreateApiKeyResponse {
+        let response = reqwest::Client::new()
+            .post(format!("{}/owner/{}/api-keys", address, owner_id))
+            .bearer_auth(session)
+            .json(key)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        response.json().await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_create_use_and_revoke_api_key(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let created = create_key(
+            &address,
+            owner_id,
+            &session,
+            &new_key(vec![Scope::OwnerRead], vec![]),
+        )
+        .await;
+
+        assert!(created.key.starts_with(&created.prefix));
+
+        let keys: Vec<ApiKey> = client
+            .get(format!("{}/owner/{}/api-keys", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(keys.len(), 1);
+
+        assert_eq!(keys[0].prefix, created.prefix);
+
+        assert_eq!(keys[0].scopes, vec![Scope::OwnerRead]);
+
+        let stored: String = sqlx::query_scalar("select key_hash from api_keys where id = $1")
+            .bind(created.id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        assert_ne!(stored, created.key);
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .header("Authorization", format!("ApiKey {}", created.key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .delete(format!("{}/owner/{}", &address, owner_id))
+            .header("Authorization", format!("ApiKey {}", created.key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .delete(format!(
+                "{}/owner/{}/api-keys/{}",
+                &address, owner_id, created.id
+            ))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .header("Authorization", format!("ApiKey {}", created.key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn test_api_key_expiry_and_ip_allowlist(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let elsewhere = create_key(
+            &address,
+            owner_id,
+            &session,
+            &new_key(vec![Scope::OwnerRead], vec!["10.0.0.0/8"]),
+        )
+        .await;
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .header("Authorization", format!("ApiKey {}", elsewhere.key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let local = create_key(
+            &address,
+            owner_id,
+            &session,
+            &new_key(vec![Scope::OwnerRead], vec!["127.0.0.1/32"]),
+        )
+        .await;
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .header("Authorization", format!("ApiKey {}", local.key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        sqlx::query("update api_keys set expires_at = now() - interval '1 minute' where id = $1")
+            .bind(local.id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .header("Authorization", format!("ApiKey {}", local.key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn test_api_key_cannot_act_as_session(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let admin_id: i32 = sqlx::query_scalar("select id from owners where email = $1")
+            .bind("colonel@patriots.test")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/owner/{}/api-keys", &address, admin_id))
+            .bearer_auth(&admin)
+            .json(&new_key(vec![], vec![]))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let created = create_key(
+            &address,
+            admin_id,
+            &admin,
+            &new_key(vec![Scope::OwnerRead, Scope::OwnerWrite], vec![]),
+        )
+        .await;
+
+        let authorization = format!("ApiKey {}", created.key);
+
+        let response = client
+            .get(format!("{}/admin/owner", &address))
+            .header("Authorization", &authorization)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .post(format!("{}/owner/{}/api-keys", &address, admin_id))
+            .header("Authorization", &authorization)
+            .json(&new_key(vec![Scope::OwnerRead], vec![]))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .delete(format!("{}/session", &address))
+            .header("Authorization", &authorization)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+}
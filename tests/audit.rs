@@ -0,0 +1,228 @@
+use chrono::{Duration, Utc};
+use proximity_service::{ApiPayload, AuditRecord, Role, UpdatePassword, UpdateProfile};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn audit_events(
+        address: &str,
+        admin: &str,
+        query: &[(&str, String)],
+    ) -> Vec<AuditRecord> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/admin/audit-events", address))
+            .bearer_auth(admin)
+            .query(query)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        response.json().await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_profile_update_is_audited(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let response = reqwest::Client::new()
+            .patch(format!("{}/owner/{}/profile", &address, owner_id))
+            .bearer_auth(&session)
+            .header("x-request-id", "audit-profile-update")
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .json(&ApiPayload {
+                payload: UpdateProfile {
+                    name: String::from("Jack"),
+                    owner_id,
+                },
+            })
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let events = audit_events(
+            &address,
+            &admin,
+            &[
+                ("target_type", String::from("owner")),
+                ("target_id", owner_id.to_string()),
+            ],
+        )
+        .await;
+
+        let actions: Vec<&str> = events.iter().map(|event| event.action.as_str()).collect();
+
+        assert_eq!(actions, vec!["owner_profile_updated", "owner_created"]);
+
+        let update = &events[0];
+
+        assert_eq!(update.actor_owner_id, Some(owner_id));
+
+        assert!(update
+            .actor_credential
+            .as_deref()
+            .unwrap()
+            .starts_with("session:"));
+
+        assert_eq!(update.request_id.as_deref(), Some("audit-profile-update"));
+
+        assert_eq!(update.diff["name"]["before"], "Raiden");
+
+        assert_eq!(update.diff["name"]["after"], "Jack");
+
+        assert!(update.diff.get("email").is_none());
+
+        assert_eq!(events[1].actor_owner_id, None);
+    }
+
+    #[sqlx::test]
+    async fn test_password_change_is_redacted(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let response = reqwest::Client::new()
+            .patch(format!("{}/owner/{}/password", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .json(&ApiPayload {
+                payload: UpdatePassword {
+                    current_password: String::from("lalilulelo"),
+                    new_password: String::from("snake-eater"),
+                },
+            })
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let diff: serde_json::Value = sqlx::query_scalar(
+            "select diff from audit_events where target_id = $1 and action = 'owner_password_changed'",
+        )
+        .bind(owner_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(diff["password"]["before"], "[REDACTED]");
+
+        assert_eq!(diff["password"]["after"], "[REDACTED]");
+    }
+
+    #[sqlx::test]
+    async fn test_audit_query_filters_and_requires_permission(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/admin/audit-events", &address))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let target = ("target_id", owner_id.to_string());
+
+        let an_hour_ago = (Utc::now() - Duration::hours(1)).to_rfc3339();
+
+        let in_an_hour = (Utc::now() + Duration::hours(1)).to_rfc3339();
+
+        let events = audit_events(
+            &address,
+            &admin,
+            &[target.clone(), ("from", an_hour_ago.clone())],
+        )
+        .await;
+
+        assert_eq!(events.len(), 1);
+
+        assert_eq!(events[0].target_id, Some(owner_id));
+
+        let events = audit_events(&address, &admin, &[target.clone(), ("from", in_an_hour)]).await;
+
+        assert!(events.is_empty());
+
+        let events = audit_events(&address, &admin, &[target, ("to", an_hour_ago)]).await;
+
+        assert!(events.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_audit_log_is_append_only_until_erasure(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let tampered =
+            sqlx::query("update audit_events set action = 'nothing' where target_id = $1")
+                .bind(owner_id)
+                .execute(&db)
+                .await;
+
+        assert!(tampered.is_err());
+
+        let deleted = sqlx::query("delete from audit_events where target_id = $1")
+            .bind(owner_id)
+            .execute(&db)
+            .await;
+
+        assert!(deleted.is_err());
+
+        let response = reqwest::Client::new()
+            .delete(format!("{}/owner/{}?erase=true", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let diffs: Vec<serde_json::Value> = sqlx::query_scalar(
+            "select diff from audit_events where target_type = 'owner' and target_id = $1 order by id",
+        )
+        .bind(owner_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(diffs.len(), 2);
+
+        for diff in &diffs {
+            assert!(!diff.to_string().contains("raiden"));
+
+            assert_eq!(diff["email"]["before"], "[ERASED]");
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    /// Reads Server-Sent Events off a streaming response, skipping keep-alive comments.
+    struct EventStream {
+        response: reqwest::Response,
+        buffer: String,
+    }
+
+    impl EventStream {
+        async fn open(address: &str, session: &str, last_event_id: Option<i64>) -> Self {
+            let mut request = reqwest::Client::new()
+                .get(format!("{}/events/stream", address))
+                .bearer_auth(session);
+
+            if let Some(id) = last_event_id {
+                request = request.header("Last-Event-ID", id.to_string());
+            }
+
+            let response = request.send().await.unwrap();
+
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+            assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+            EventStream {
+                response,
+                buffer: String::new(),
+            }
+        }
+
+        /// Returns the next event as `(id, event type, data)`.
+        async fn next(&mut self) -> (i64, String, serde_json::Value) {
+            loop {
+                while let Some(end) = self.buffer.find("\n\n") {
+                    let frame: String = self.buffer.drain(..end + 2).collect();
+
+                    let field = |name: &str| {
+                        frame
+                            .lines()
+                            .find_map(|line| line.strip_prefix(name))
+                            .map(|value| value.trim_start().to_string())
+                    };
+
+                    if let (Some(id), Some(event), Some(data)) =
+                        (field("id:"), field("event:"), field("data:"))
+                    {
+                        return (
+                            id.parse().unwrap(),
+                            event,
+                            serde_json::from_str(&data).unwrap(),
+                        );
+                    }
+                }
+
+                let chunk = tokio::time::timeout(Duration::from_secs(5), self.response.chunk())
+                    .await
+                    .expect("Timed out waiting for an event")
+                    .unwrap()
+                    .expect("Event stream ended");
+
+                self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+        }
+
+        /// Waits for the server to close the stream, ignoring anything still sent before.
+        async fn wait_for_end(&mut self) {
+            loop {
+                let chunk = tokio::time::timeout(Duration::from_secs(5), self.response.chunk())
+                    .await
+                    .expect("Timed out waiting for the stream to end")
+                    .unwrap();
+
+                if chunk.is_none() {
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn rename(address: &str, session: &str, owner_id: i32, name: &str) {
+        let response = reqwest::Client::new()
+            .patch(format!("{}/owner/{}", address, owner_id))
+            .bearer_auth(session)
+            .header("If-Match", utils::etag(address, session, owner_id).await)
+            .header("Content-Type", "application/merge-patch+json")
+            .body(serde_json::json!({ "name": name }).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn test_stream_pushes_own_owner_changes(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other_id =
+            utils::create_owner_with_password(&db, "otacon@philanthropy.test", "hal-emmerich")
+                .await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other = utils::login(&address, "otacon@philanthropy.test", "hal-emmerich").await;
+
+        let mut stream = EventStream::open(&address, &session, None).await;
+
+        rename(&address, &other, other_id, "Hal").await;
+
+        rename(&address, &session, owner_id, "Jack").await;
+
+        let (id, event, data) = stream.next().await;
+
+        assert!(id > 0);
+
+        assert_eq!(event, "owner.updated");
+
+        assert_eq!(data["aggregate_id"], owner_id);
+
+        assert_eq!(data["payload"]["changed"], serde_json::json!(["name"]));
+    }
+
+    #[sqlx::test]
+    async fn test_stream_resumes_from_last_event_id(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        rename(&address, &session, owner_id, "Jack").await;
+
+        rename(&address, &session, owner_id, "Snake").await;
+
+        let ids: Vec<i64> = sqlx::query_scalar(
+            "select id from outbox where aggregate_id = $1 and event_type = 'owner.updated' order by id",
+        )
+        .bind(owner_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        let mut stream = EventStream::open(&address, &session, Some(ids[0])).await;
+
+        let (id, _, data) = stream.next().await;
+
+        assert_eq!(id, ids[1]);
+
+        assert_eq!(data["payload"]["version"], 3);
+
+        rename(&address, &session, owner_id, "Big Boss").await;
+
+        let (id, _, data) = stream.next().await;
+
+        assert!(id > ids[1]);
+
+        assert_eq!(data["payload"]["version"], 4);
+    }
+
+    #[sqlx::test]
+    async fn test_stream_requires_authentication(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/events/stream", &address))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(format!("{}/events/stream", &address))
+            .bearer_auth(&session)
+            .header("Last-Event-ID", "yesterday")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn test_stream_ends_after_logout(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.event_stream_auth_check_secs = 1;
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let mut stream = EventStream::open(&address, &session, None).await;
+
+        rename(&address, &session, owner_id, "Jack").await;
+
+        let (_, event, _) = stream.next().await;
+
+        assert_eq!(event, "owner.updated");
+
+        let response = reqwest::Client::new()
+            .delete(format!("{}/session", &address))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert!(response.status().is_success());
+
+        stream.wait_for_end().await;
+    }
+}
@@ -0,0 +1,187 @@
+use futures::future::BoxFuture;
+use opentelemetry::{
+    sdk::{
+        export::{
+            metrics::aggregation::cumulative_temporality_selector,
+            trace::{ExportResult, SpanData, SpanExporter},
+        },
+        metrics::{controllers, processors, selectors},
+        trace::TracerProvider,
+    },
+    trace::{Status, TracerProvider as _},
+    Key, Value,
+};
+use prometheus::{Encoder, TextEncoder};
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Registry};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct Exported(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Exported {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.0.lock().unwrap().extend(batch);
+
+            Box::pin(std::future::ready(Ok(())))
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_failed_requests_are_traced_logged_and_counted(db: PgPool) {
+        let logs = Captured::default();
+
+        let spans = Exported::default();
+
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(spans.clone())
+            .build();
+
+        let tracer = provider.tracer("tests");
+
+        opentelemetry::global::set_tracer_provider(provider);
+
+        tracing::subscriber::set_global_default(
+            Registry::default()
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(logs.clone())
+                        .with_ansi(false),
+                ),
+        )
+        .unwrap();
+
+        let metrics = opentelemetry_prometheus::exporter(
+            controllers::basic(processors::factory(
+                selectors::simple::inexpensive(),
+                cumulative_temporality_selector(),
+            ))
+            .build(),
+        )
+        .try_init()
+        .unwrap();
+
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        sqlx::query("DROP TABLE webhook_subscriptions CASCADE")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/owner/{}/webhooks", &address, owner_id))
+            .bearer_auth(&session)
+            .header("x-request-id", "failing-request-1")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let mut span = None;
+
+        for _ in 0..50 {
+            span = spans
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|span| {
+                    span.name == "http-request"
+                        && span.attributes.get(&Key::new("http.failure_kind"))
+                            == Some(&Value::from("server_error"))
+                })
+                .cloned();
+
+            if span.is_some() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let span = span.expect("Expected the failed request's span");
+
+        assert!(matches!(
+            &span.status,
+            Status::Error { description } if description == "500 Internal Server Error"
+        ));
+
+        // A status code isn't an exception.
+        assert!(span.attributes.get(&Key::new("exception.type")).is_none());
+
+        assert!(span
+            .attributes
+            .get(&Key::new("exception.message"))
+            .is_none());
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+
+        let failure = logs
+            .lines()
+            .find(|line| line.contains("request failed"))
+            .expect("Expected the failure to be logged");
+
+        assert!(failure.contains("ERROR"));
+
+        assert!(failure.contains("failing-request-1"));
+
+        assert!(failure.contains("server_error"));
+
+        let mut body = Vec::new();
+
+        TextEncoder::new()
+            .encode(&metrics.registry().gather(), &mut body)
+            .unwrap();
+
+        let body = String::from_utf8(body).unwrap();
+
+        assert!(body
+            .lines()
+            .any(|line| line.starts_with("http_server_failures_total")
+                && line.contains(r#"failure_class="server_error""#)
+                && line.ends_with(" 1")));
+    }
+}
@@ -1,3 +1,4 @@
+use proximity_service::{HealthStatus, Readiness};
 use sqlx::PgPool;
 
 mod utils;
@@ -9,7 +10,7 @@ async fn test_health_check(db: PgPool) {
     let client = reqwest::Client::new();
 
     let response = client
-        .get(&format!("{}/health_check", &address))
+        .get(format!("{}/health_check", &address))
         .send()
         .await
         .expect("Failed to execute request");
@@ -18,3 +19,119 @@ async fn test_health_check(db: PgPool) {
 
     assert_eq!(Some(0), response.content_length());
 }
+
+#[sqlx::test]
+async fn test_liveness(db: PgPool) {
+    let (address, _) = utils::make_server(db).await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/health/live", &address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    assert_eq!(body["status"], "ok");
+}
+
+#[sqlx::test]
+async fn test_readiness_reports_each_component(db: PgPool) {
+    let (address, _) = utils::make_server(db).await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/health/ready", &address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let readiness: Readiness = response.json().await.unwrap();
+
+    assert_eq!(readiness.status, HealthStatus::Ok);
+
+    let components: Vec<&str> = readiness.checks.keys().map(String::as_str).collect();
+
+    assert_eq!(
+        components,
+        vec!["database", "migrations", "pool", "telemetry"]
+    );
+
+    assert!(readiness.checks["database"].critical);
+
+    assert_eq!(
+        readiness.checks["migrations"].details["pending"],
+        serde_json::json!([])
+    );
+
+    assert!(!readiness.checks["telemetry"].critical);
+
+    assert_eq!(
+        readiness.checks["telemetry"].details["exporter"],
+        "disabled"
+    );
+}
+
+#[sqlx::test]
+async fn test_readiness_fails_on_missing_migration(db: PgPool) {
+    let (address, db) = utils::make_server(db).await;
+
+    let version: i64 =
+        sqlx::query_scalar("delete from _sqlx_migrations where version = (select max(version) from _sqlx_migrations) returning version")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/health/ready", &address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    let readiness: Readiness = response.json().await.unwrap();
+
+    assert_eq!(readiness.status, HealthStatus::Failing);
+
+    assert_eq!(readiness.checks["migrations"].status, HealthStatus::Failing);
+
+    assert_eq!(
+        readiness.checks["migrations"].details["pending"],
+        serde_json::json!([version])
+    );
+
+    assert_eq!(readiness.checks["database"].status, HealthStatus::Ok);
+}
+
+#[sqlx::test]
+async fn test_readiness_fails_without_database(db: PgPool) {
+    let (address, db) = utils::make_server(db).await;
+
+    db.close().await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/health/ready", &address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    let readiness: Readiness = response.json().await.unwrap();
+
+    assert_eq!(readiness.checks["database"].status, HealthStatus::Failing);
+
+    assert!(readiness.checks["database"].message.is_some());
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/health/live", &address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
@@ -0,0 +1,231 @@
+use proximity_service::{
+    ApiPayload, CreateOwner, CreateOwnerResponse, CreateSession, CreateSessionResponse,
+};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn new_owner(email: &str) -> ApiPayload<CreateOwner> {
+        ApiPayload {
+            payload: CreateOwner {
+                name: String::from("Raiden"),
+                email: String::from(email),
+                password: String::from("lalilulelo"),
+            },
+        }
+    }
+
+    async fn post_owner(address: &str, key: &str, email: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/owner", address))
+            .header("Idempotency-Key", key)
+            .json(&new_owner(email))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    async fn count_owners(db: &PgPool) -> i64 {
+        sqlx::query_scalar("select count(*) from owners")
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_retry_replays_stored_response(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let response = post_owner(&address, "retry-1", "raiden@dead-cell.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        assert!(response.headers().get("idempotent-replayed").is_none());
+
+        let created: CreateOwnerResponse = response.json().await.unwrap();
+
+        let response = post_owner(&address, "retry-1", "raiden@dead-cell.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        assert_eq!(response.headers()["idempotent-replayed"], "true");
+
+        let replayed: CreateOwnerResponse = response.json().await.unwrap();
+
+        assert_eq!(replayed.id, created.id);
+
+        assert_eq!(count_owners(&db).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn test_key_reused_with_different_body(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let response = post_owner(&address, "retry-1", "raiden@dead-cell.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        let response = post_owner(&address, "retry-1", "snake@fox-hound.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = post_owner(&address, "retry-2", "snake@fox-hound.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        assert_eq!(count_owners(&db).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_expired_key_can_be_reused(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let response = post_owner(&address, "retry-1", "raiden@dead-cell.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        sqlx::query("update idempotency_keys set expires_at = now() - interval '1 minute'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let response = post_owner(&address, "retry-1", "snake@fox-hound.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        assert!(response.headers().get("idempotent-replayed").is_none());
+
+        assert_eq!(count_owners(&db).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_abandoned_key_can_be_reclaimed(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let response = post_owner(&address, "retry-1", "raiden@dead-cell.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        // As left behind by a request that was dropped before storing its response.
+        sqlx::query(
+            "update idempotency_keys set status_code = null, response_headers = null, \
+             response_body = null, locked_until = now() + interval '1 minute'",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let response = post_owner(&address, "retry-1", "raiden@dead-cell.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        sqlx::query("update idempotency_keys set locked_until = now() - interval '1 second'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let response = post_owner(&address, "retry-1", "snake@fox-hound.test").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        let response = post_owner(&address, "retry-1", "snake@fox-hound.test").await;
+
+        assert_eq!(response.headers()["idempotent-replayed"], "true");
+
+        assert_eq!(count_owners(&db).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_anonymous_keys_are_scoped_to_the_client(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.client_ip_header = Some(String::from("x-forwarded-for"));
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        for (ip, email) in [
+            ("203.0.113.7", "raiden@dead-cell.test"),
+            ("198.51.100.23", "snake@fox-hound.test"),
+        ] {
+            let response = reqwest::Client::new()
+                .post(format!("{}/owner", &address))
+                .header("Idempotency-Key", "signup")
+                .header("X-Forwarded-For", ip)
+                .json(&new_owner(email))
+                .send()
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+            assert!(response.headers().get("idempotent-replayed").is_none());
+        }
+
+        assert_eq!(count_owners(&db).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_oversized_body_is_rejected(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/owner", &address))
+            .header("Idempotency-Key", "retry-1")
+            .json(&new_owner(&format!(
+                "{}@dead-cell.test",
+                "r".repeat(70_000)
+            )))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+
+        assert_eq!(count_owners(&db).await, 0);
+    }
+
+    #[sqlx::test]
+    async fn test_credentials_are_never_stored(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let credentials = ApiPayload {
+            payload: CreateSession {
+                email: String::from("raiden@dead-cell.test"),
+                password: String::from("lalilulelo"),
+            },
+        };
+
+        let mut tokens = Vec::new();
+
+        for _ in 0..2 {
+            let response = reqwest::Client::new()
+                .post(format!("{}/session", &address))
+                .header("Idempotency-Key", "login-1")
+                .json(&credentials)
+                .send()
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+            let session: CreateSessionResponse = response.json().await.unwrap();
+
+            tokens.push(session.token);
+        }
+
+        assert_ne!(tokens[0], tokens[1]);
+
+        let stored: i64 = sqlx::query_scalar("select count(*) from idempotency_keys")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        assert_eq!(stored, 0);
+    }
+}
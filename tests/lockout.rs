@@ -0,0 +1,166 @@
+use proximity_service::{ApiPayload, CreateSession, Role};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn attempt_login(address: &str, email: &str, password: &str) -> reqwest::Response {
+        let credentials = ApiPayload {
+            payload: CreateSession {
+                email: String::from(email),
+                password: String::from(password),
+            },
+        };
+
+        reqwest::Client::new()
+            .post(format!("{}/session", address))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_account_lockout_and_staff_unlock(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.login_backoff_base_ms = 0;
+
+        settings.login_max_account_failures = 3;
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        for _ in 0..3 {
+            let response = attempt_login(&address, "raiden@dead-cell.test", "wrong-password").await;
+
+            assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        }
+
+        let response = attempt_login(&address, "Raiden@Dead-Cell.test", "lalilulelo").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+        assert!(response.headers().contains_key("retry-after"));
+
+        let response = reqwest::Client::new()
+            .delete(format!("{}/admin/owner/{}/lockout", &address, owner_id))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = attempt_login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        let events: Vec<(String, Option<i32>)> = sqlx::query_as(
+            "select action, actor_owner_id from audit_events where target_type = 'owner' and target_id = $1 and action like 'login_%' order by id",
+        )
+        .bind(owner_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0], (String::from("login_locked"), None));
+
+        assert_eq!(events[1].0, "login_unlocked");
+
+        assert!(events[1].1.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_exponential_backoff_after_failure(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.login_backoff_base_ms = 5000;
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let response = attempt_login(&address, "raiden@dead-cell.test", "wrong-password").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = attempt_login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+        assert_eq!(response.headers()["retry-after"], "5");
+    }
+
+    #[sqlx::test]
+    async fn test_ip_lockout_across_accounts(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.login_backoff_base_ms = 0;
+
+        settings.login_max_ip_failures = 2;
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        for email in ["vamp@dead-cell.test", "fortune@dead-cell.test"] {
+            let response = attempt_login(&address, email, "wrong-password").await;
+
+            assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        }
+
+        let response = attempt_login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+        let response = reqwest::Client::new()
+            .delete(format!("{}/admin/lockout/ip/127.0.0.1", &address))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = attempt_login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        let events: Vec<(String, Option<i32>, serde_json::Value)> = sqlx::query_as(
+            "select action, target_id, diff from audit_events where target_type = 'client_ip' order by id",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                (
+                    String::from("login_locked"),
+                    None,
+                    serde_json::json!({ "ip": { "before": null, "after": "127.0.0.1" } })
+                ),
+                (
+                    String::from("login_unlocked"),
+                    None,
+                    serde_json::json!({ "ip": { "before": "127.0.0.1", "after": null } })
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use proximity_service::{init_telemetry, ApiPayload, LogLevelStatus, Role, UpdateLogLevel};
+use std::time::Duration;
+use tracing::Level;
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn update(directives: &str, revert_after_secs: Option<u64>) -> ApiPayload<UpdateLogLevel> {
+        ApiPayload {
+            payload: UpdateLogLevel {
+                directives: String::from(directives),
+                revert_after_secs,
+            },
+        }
+    }
+
+    fn debug_enabled() -> bool {
+        tracing::enabled!(target: "log_level", Level::DEBUG)
+    }
+
+    #[sqlx::test]
+    async fn test_log_level_changes_at_runtime_and_reverts(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.log_level = String::from("info");
+
+        let _guard = init_telemetry(&settings).unwrap();
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let client = reqwest::Client::new();
+
+        let url = format!("{}/admin/log-level", &address);
+
+        let response = client
+            .put(&url)
+            .json(&update("debug", None))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .put(&url)
+            .bearer_auth(&support)
+            .json(&update("debug", None))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .put(&url)
+            .bearer_auth(&admin)
+            .json(&update("log_level=loud", None))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        assert!(!debug_enabled());
+
+        let response = client
+            .put(&url)
+            .bearer_auth(&admin)
+            .json(&update("info,log_level=debug", Some(1)))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let status = response.json::<LogLevelStatus>().await.unwrap();
+
+        assert!(status.directives.contains("log_level=debug"));
+
+        assert!(status.revert_at.is_some());
+
+        assert!(debug_enabled());
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert!(!debug_enabled());
+
+        let status = client
+            .get(&url)
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap()
+            .json::<LogLevelStatus>()
+            .await
+            .unwrap();
+
+        assert_eq!(status.directives, "info");
+
+        assert!(status.revert_at.is_none());
+
+        client
+            .put(&url)
+            .bearer_auth(&admin)
+            .json(&update("info,log_level=debug", Some(1)))
+            .send()
+            .await
+            .unwrap();
+
+        let response = client
+            .put(&url)
+            .bearer_auth(&admin)
+            .json(&update("info,log_level=debug", None))
+            .send()
+            .await
+            .unwrap();
+
+        let status = response.json::<LogLevelStatus>().await.unwrap();
+
+        assert!(status.revert_at.is_none());
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert!(debug_enabled());
+        let changes: Vec<(String, Option<i32>)> = sqlx::query_as(
+            "select action, target_id from audit_events where target_type = 'log_level' order by id",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(changes.len(), 3);
+
+        assert!(changes
+            .iter()
+            .all(|change| change == &(String::from("log_level_changed"), None)));
+    }
+}
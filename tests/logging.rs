@@ -0,0 +1,93 @@
+use proximity_service::{init_telemetry, LogFormat, LogRotation, TelemetryExporter};
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    #[sqlx::test]
+    async fn test_json_logs_are_correlated_with_traces(db: PgPool) {
+        let dir = std::env::temp_dir()
+            .join("proximity-service-logs")
+            .join(Uuid::new_v4().to_string());
+
+        let mut settings = utils::make_settings();
+
+        settings.log_format = LogFormat::Json;
+
+        settings.log_file_dir = Some(dir.to_string_lossy().into_owned());
+
+        settings.log_file_rotation = LogRotation::Never;
+
+        settings.telemetry_exporter = TelemetryExporter::OtlpGrpc;
+
+        settings.otel_endpoint = String::from("http://127.0.0.1:1");
+
+        settings.otel_timeout_ms = 100;
+
+        let log_file = dir.join(&settings.log_file_prefix);
+
+        let guard = init_telemetry(&settings).unwrap();
+
+        let (address, _) = utils::make_server_with_settings(db, settings).await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/health_check", &address))
+            .header("x-request-id", "logged-request-1")
+            .header(
+                "traceparent",
+                format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let mut line = None;
+
+        for _ in 0..50 {
+            line = std::fs::read_to_string(&log_file)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| serde_json::from_str::<Value>(line).unwrap())
+                .find(|line| line["fields"]["message"] == "finished processing request");
+
+            if line.is_some() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let line = line.expect("Expected the response to be logged to the file");
+
+        assert_eq!(line["level"], "INFO");
+
+        assert_eq!(line["trace_id"], TRACE_ID);
+
+        let span_id = line["span_id"].as_str().unwrap();
+
+        assert_eq!(span_id.len(), 16);
+
+        assert_ne!(span_id, PARENT_SPAN_ID);
+
+        assert_eq!(line["span"]["name"], "http-request");
+
+        assert!(line["span"]["request_id"]
+            .as_str()
+            .unwrap()
+            .contains("logged-request-1"));
+
+        drop(guard);
+    }
+}
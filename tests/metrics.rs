@@ -0,0 +1,191 @@
+use opentelemetry::{
+    sdk::{
+        export::metrics::{
+            aggregation::{cumulative_temporality_selector, Count, LastValue, Sum},
+            InstrumentationLibraryReader,
+        },
+        metrics::{
+            aggregators::{HistogramAggregator, LastValueAggregator, SumAggregator},
+            controllers::{self, BasicController},
+            processors, selectors,
+        },
+    },
+    Context,
+};
+use std::collections::BTreeMap;
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    /// One collected series: the instrument, its attributes and its value. Counters report
+    /// their sum, histograms their count and gauges their last value.
+    type Series = (String, BTreeMap<String, String>, f64);
+
+    fn install_controller() -> BasicController {
+        let controller = controllers::basic(processors::factory(
+            selectors::simple::histogram([10.0, 100.0, 1000.0]),
+            cumulative_temporality_selector(),
+        ))
+        .build();
+
+        opentelemetry::global::set_meter_provider(controller.clone());
+
+        controller
+    }
+
+    fn collect(controller: &BasicController) -> Vec<Series> {
+        controller.collect(&Context::current()).unwrap();
+
+        let mut series = Vec::new();
+
+        controller
+            .try_for_each(&mut |_, reader| {
+                reader.try_for_each(&cumulative_temporality_selector(), &mut |record| {
+                    let kind = record.descriptor().number_kind();
+
+                    let aggregator = record.aggregator().unwrap().as_any();
+
+                    let value = if let Some(histogram) =
+                        aggregator.downcast_ref::<HistogramAggregator>()
+                    {
+                        histogram.count()? as f64
+                    } else if let Some(sum) = aggregator.downcast_ref::<SumAggregator>() {
+                        sum.sum()?.to_f64(kind)
+                    } else if let Some(last) = aggregator.downcast_ref::<LastValueAggregator>() {
+                        last.last_value()?.0.to_f64(kind)
+                    } else {
+                        return Ok(());
+                    };
+
+                    let attributes = record
+                        .attributes()
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect();
+
+                    series.push((record.descriptor().name().to_string(), attributes, value));
+
+                    Ok(())
+                })
+            })
+            .unwrap();
+
+        series
+    }
+
+    fn value(series: &[Series], name: &str, attributes: &[(&str, &str)]) -> Option<f64> {
+        series
+            .iter()
+            .find(|(series_name, series_attributes, _)| {
+                series_name == name
+                    && attributes.iter().all(|(key, value)| {
+                        series_attributes.get(*key).map(String::as_str) == Some(*value)
+                    })
+            })
+            .map(|(_, _, value)| *value)
+    }
+
+    #[sqlx::test]
+    async fn test_requests_and_pool_are_measured(db: PgPool) {
+        let controller = install_controller();
+
+        let settings = utils::make_settings();
+
+        let max_connections = settings.db_max_connections;
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        for id in [owner_id, owner_id + 1000] {
+            client
+                .get(format!("{}/owner/{}", &address, id))
+                .bearer_auth(&session)
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let response = client
+            .get(format!("{}/no/such/route", &address))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = client
+            .get(format!("{}/metrics", &address))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let series = collect(&controller);
+
+        let owner_route = [("http.route", "/owner/:id"), ("http.method", "GET")];
+
+        let requests: f64 = series
+            .iter()
+            .filter(|(name, attributes, _)| {
+                name == "http.server.requests"
+                    && attributes.get("http.route").map(String::as_str) == Some("/owner/:id")
+            })
+            .map(|(_, _, value)| value)
+            .sum();
+
+        assert_eq!(requests, 2.0);
+
+        assert_eq!(
+            value(
+                &series,
+                "http.server.requests",
+                &[owner_route[0], owner_route[1], ("http.status_class", "2xx")]
+            ),
+            Some(1.0)
+        );
+
+        assert!(value(&series, "http.server.duration", &owner_route).is_some());
+
+        let raw_path = format!("/owner/{}", owner_id);
+
+        assert!(!series
+            .iter()
+            .any(|(_, attributes, _)| attributes.get("http.route") == Some(&raw_path)));
+
+        assert_eq!(
+            value(
+                &series,
+                "http.server.requests",
+                &[("http.route", "unmatched"), ("http.status_class", "4xx")]
+            ),
+            Some(1.0)
+        );
+
+        assert_eq!(
+            value(
+                &series,
+                "http.server.errors",
+                &[("http.route", "unmatched")]
+            ),
+            None
+        );
+
+        assert_eq!(
+            value(&series, "db.client.connections.max", &[]),
+            Some(max_connections as f64)
+        );
+
+        assert!(value(&series, "db.client.connections.usage", &[("state", "idle")]).is_some());
+    }
+}
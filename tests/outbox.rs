@@ -0,0 +1,316 @@
+use axum::{http::StatusCode, routing::post, Router};
+use chrono::Utc;
+use proximity_service::{prune_dispatched_events, DomainEvent, EventSinkKind};
+use std::{
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    /// Polls the events file until `count` events for the owner were published.
+    async fn wait_for_events(path: &str, owner_id: i32, count: usize) -> Vec<DomainEvent> {
+        for _ in 0..50 {
+            let events: Vec<DomainEvent> = std::fs::read_to_string(path)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .filter(|event: &DomainEvent| event.aggregate_id == owner_id)
+                .collect();
+
+            if events.len() >= count {
+                return events;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("Expected {} event(s) to be published", count)
+    }
+
+    #[sqlx::test]
+    async fn test_owner_changes_are_published(db: PgPool) {
+        let settings = utils::make_settings();
+
+        let path = settings.event_sink_file_path.clone();
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .patch(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .header("Content-Type", "application/merge-patch+json")
+            .body(serde_json::json!({ "name": "Jack" }).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .delete(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let events = wait_for_events(&path, owner_id, 3).await;
+
+        let types: Vec<&str> = events
+            .iter()
+            .map(|event| event.event_type.as_str())
+            .collect();
+
+        assert_eq!(
+            types,
+            vec!["owner.created", "owner.updated", "owner.deleted"]
+        );
+
+        assert!(events.iter().all(|event| event.aggregate_type == "owner"));
+
+        assert_eq!(
+            events[0].payload,
+            serde_json::json!({ "id": owner_id, "version": 1 })
+        );
+
+        assert_eq!(
+            events[1].payload,
+            serde_json::json!({ "id": owner_id, "version": 2, "changed": ["name"] })
+        );
+
+        assert_eq!(events[2].payload, serde_json::json!({ "id": owner_id }));
+    }
+
+    #[sqlx::test]
+    async fn test_prune_dispatched_events(db: PgPool) {
+        let expired =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let recent =
+            utils::create_owner_with_password(&db, "snake@fox-hound.test", "lalilulelo").await;
+
+        let pending =
+            utils::create_owner_with_password(&db, "otacon@philanthropy.test", "hal").await;
+
+        let delivered =
+            utils::create_owner_with_password(&db, "meryl@fox-hound.test", "lalilulelo").await;
+
+        sqlx::query(
+            "update outbox set dispatched_at = now() - interval '8 days' where aggregate_id = any($1)",
+        )
+        .bind(vec![expired, delivered])
+        .execute(&db)
+        .await
+        .unwrap();
+
+        // Its delivery log keeps the event around.
+        sqlx::query(
+            "with subscription as ( \
+                 insert into webhook_subscriptions (owner_id, url, event_types, secret) \
+                 values ($1, 'https://localhost/hooks', '{owner.created}', 'whsec_test') \
+                 returning id \
+             ) \
+             insert into webhook_deliveries (subscription_id, event_id, status) \
+             select subscription.id, outbox.id, 'delivered' \
+             from subscription, outbox where outbox.aggregate_id = $1",
+        )
+        .bind(delivered)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        sqlx::query(
+            "update outbox set dispatched_at = now() - interval '1 day' where aggregate_id = $1",
+        )
+        .bind(recent)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let pruned = prune_dispatched_events(Utc::now() - chrono::Duration::days(7), &db)
+            .await
+            .unwrap();
+
+        assert_eq!(pruned, 1);
+
+        let remaining: Vec<i32> =
+            sqlx::query_scalar("select aggregate_id from outbox order by aggregate_id")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+
+        assert_eq!(remaining, vec![recent, pending, delivered]);
+    }
+
+    #[sqlx::test]
+    async fn test_failed_deliveries_are_retried(db: PgPool) {
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+
+        let receiver = Router::new().route(
+            "/events",
+            post(move || {
+                let counter = counter.clone();
+
+                async move {
+                    match counter.fetch_add(1, Ordering::SeqCst) {
+                        0 => StatusCode::INTERNAL_SERVER_ERROR,
+                        _ => StatusCode::NO_CONTENT,
+                    }
+                }
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let receiver_address = listener.local_addr().unwrap();
+
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(receiver.into_make_service()),
+        );
+
+        let mut settings = utils::make_settings();
+
+        settings.event_sink_kind = EventSinkKind::Webhook;
+
+        settings.event_sink_webhook_url = Some(format!("http://{}/events", receiver_address));
+
+        settings.outbox_retry_base_ms = 50;
+
+        let (_address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let mut row = None;
+
+        for _ in 0..50 {
+            row = sqlx::query_as::<_, (i32, Option<String>)>(
+                "select attempts, last_error from outbox where aggregate_id = $1 and dispatched_at is not null",
+            )
+            .bind(owner_id)
+            .fetch_optional(&db)
+            .await
+            .unwrap();
+
+            if row.is_some() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let (attempts, last_error) = row.expect("Expected the event to be delivered");
+
+        assert_eq!(attempts, 1);
+
+        assert!(last_error.unwrap().contains("500"));
+
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[sqlx::test]
+    async fn test_events_are_published_outside_the_claim(db: PgPool) {
+        let started = Arc::new(Notify::new());
+
+        let release = Arc::new(Notify::new());
+
+        let (receiver_started, receiver_release) = (started.clone(), release.clone());
+
+        let receiver = Router::new().route(
+            "/events",
+            post(move || {
+                let (started, release) = (receiver_started.clone(), receiver_release.clone());
+
+                async move {
+                    started.notify_one();
+
+                    release.notified().await;
+
+                    StatusCode::NO_CONTENT
+                }
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let receiver_address = listener.local_addr().unwrap();
+
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(receiver.into_make_service()),
+        );
+
+        let mut settings = utils::make_settings();
+
+        settings.event_sink_kind = EventSinkKind::Webhook;
+
+        settings.event_sink_webhook_url = Some(format!("http://{}/events", receiver_address));
+
+        let (_address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        tokio::time::timeout(Duration::from_secs(5), started.notified())
+            .await
+            .expect("Expected the event to be published");
+
+        // Neither locked nor due while the sink is busy with it.
+        let claimed: bool = sqlx::query_scalar(
+            "select next_attempt_at > now() from outbox where aggregate_id = $1 for update nowait",
+        )
+        .bind(owner_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        assert!(claimed);
+
+        release.notify_one();
+
+        let mut dispatched = false;
+
+        for _ in 0..50 {
+            dispatched = sqlx::query_scalar(
+                "select dispatched_at is not null from outbox where aggregate_id = $1",
+            )
+            .bind(owner_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+            if dispatched {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert!(dispatched);
+    }
+}
@@ -1,6 +1,6 @@
 use proximity_service::{
-    create_owner, ApiPayload, CreateOwner, CreateOwnerResponse, Owners, UpdateCredentials,
-    UpdateProfile,
+    create_owner, hash_password, purge_deleted_owners, Actor, ApiPayload, CreateOwner,
+    CreateOwnerResponse, Owners, Role, UpdateEmail, UpdatePassword, UpdateProfile,
 };
 
 mod utils;
@@ -18,12 +18,14 @@ mod tests {
         let owner = CreateOwner {
             name: String::from("Henry"),
             email: String::from("@gmail.com"),
-            password: String::from("password"),
+            password: hash_password(String::from("password")).await.unwrap(),
         };
 
-        create_owner(owner, db).await.map(|record| TestSetup {
-            owner_id: record.id,
-        })
+        create_owner(owner, &Actor::system(), db)
+            .await
+            .map(|record| TestSetup {
+                owner_id: record.id,
+            })
     }
 
     impl Drop for TestSetup {
@@ -36,10 +38,13 @@ mod tests {
 
         let test_setup = setup(&db).await.expect("Expected to get a record");
 
+        let session = utils::login(&address, "@gmail.com", "password").await;
+
         let client = reqwest::Client::new();
 
         let response = client
-            .get(&format!("{}/owner/{}", &address, test_setup.owner_id))
+            .get(format!("{}/owner/{}", &address, test_setup.owner_id))
+            .bearer_auth(&session)
             .send()
             .await
             .unwrap();
@@ -70,7 +75,7 @@ mod tests {
         let client = reqwest::Client::new();
 
         let response = client
-            .post(&format!("{}/owner", &address))
+            .post(format!("{}/owner", &address))
             .json(&new_owner)
             .send()
             .await
@@ -83,16 +88,55 @@ mod tests {
         assert!(json_response.id > 0);
     }
 
+    #[sqlx::test]
+    async fn test_post_owner_validation(db: PgPool) {
+        let (address, _) = utils::make_server(db).await;
+
+        let client = reqwest::Client::new();
+
+        for (email, password) in [
+            ("solidsnake", "lalilulelo"),
+            ("solid snake@sonsofliberty.test", "lalilulelo"),
+            ("solidsnake@sonsofliberty.test", "foxdie"),
+        ] {
+            let new_owner = ApiPayload {
+                payload: CreateOwner {
+                    name: String::from("David Hayer"),
+                    email: String::from(email),
+                    password: String::from(password),
+                },
+            };
+
+            let response = client
+                .post(format!("{}/owner", &address))
+                .json(&new_owner)
+                .send()
+                .await
+                .unwrap();
+
+            assert_eq!(
+                response.status(),
+                reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+                "{} {}",
+                email,
+                password
+            );
+        }
+    }
+
     #[sqlx::test]
     async fn test_delete_owner(db: PgPool) {
         let (address, db) = utils::make_server(db).await;
 
         let test_setup = setup(&db).await.expect("Expected to get a record");
 
+        let session = utils::login(&address, "@gmail.com", "password").await;
+
         let client = reqwest::Client::new();
 
         let response = client
-            .delete(&format!("{}/owner/{}", &address, &test_setup.owner_id))
+            .delete(format!("{}/owner/{}", &address, &test_setup.owner_id))
+            .bearer_auth(&session)
             .send()
             .await
             .unwrap();
@@ -104,12 +148,122 @@ mod tests {
         assert_eq!(actual, expected)
     }
 
+    #[sqlx::test]
+    async fn test_delete_unknown_owner(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let test_setup = setup(&db).await.expect("Expected to get a record");
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .delete(format!("{}/owner/{}", &address, test_setup.owner_id + 1000))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = client
+            .delete(format!("{}/owner/{}", &address, test_setup.owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = client
+            .delete(format!("{}/owner/{}", &address, test_setup.owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_deleted_owner_is_hidden(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let test_setup = setup(&db).await.expect("Expected to get a record");
+
+        let session = utils::login(&address, "@gmail.com", "password").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let client = reqwest::Client::new();
+
+        client
+            .delete(format!("{}/owner/{}", &address, &test_setup.owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, test_setup.owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let deleted_at: Option<chrono::DateTime<chrono::Utc>> =
+            sqlx::query_scalar(r#"select deleted_at from "owners" where id = $1"#)
+                .bind(test_setup.owner_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+
+        assert!(deleted_at.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_purge_deleted_owners(db: PgPool) {
+        let expired = setup(&db).await.expect("Expected to get a record");
+
+        sqlx::query(r#"update "owners" set deleted_at = now() - interval '31 days' where id = $1"#)
+            .bind(expired.owner_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let recent = setup(&db).await.expect("Expected to get a record");
+
+        sqlx::query(r#"update "owners" set deleted_at = now() - interval '1 day' where id = $1"#)
+            .bind(recent.owner_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let purged = purge_deleted_owners(30, &db).await.unwrap();
+
+        assert_eq!(purged, 1);
+
+        let remaining: Vec<i32> = sqlx::query_scalar(r#"select id from "owners""#)
+            .fetch_all(&db)
+            .await
+            .unwrap();
+
+        assert_eq!(remaining, vec![recent.owner_id]);
+    }
+
     #[sqlx::test]
     async fn test_update_profile_information(db: PgPool) {
         let (address, db) = utils::make_server(db).await;
 
         let test_setup = setup(&db).await.expect("Somethin");
 
+        let session = utils::login(&address, "@gmail.com", "password").await;
+
         let client = reqwest::Client::new();
 
         let patch = ApiPayload {
@@ -124,6 +278,11 @@ mod tests {
                 "{}/owner/{}/profile",
                 &address, &test_setup.owner_id
             ))
+            .bearer_auth(&session)
+            .header(
+                "If-Match",
+                utils::etag(&address, &session, test_setup.owner_id).await,
+            )
             .json(&patch)
             .send()
             .await
@@ -137,32 +296,567 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn test_update_credentials(db: PgPool) {
+    async fn test_update_profile_follows_the_url(db: PgPool) {
         let (address, db) = utils::make_server(db).await;
 
-        let owner_id = utils::test_setup(&db).await;
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other_owner_id = utils::test_setup(&db).await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
 
         let client = reqwest::Client::new();
 
         let patch = ApiPayload {
-            payload: UpdateCredentials {
+            payload: UpdateProfile {
+                name: String::from("Jack"),
+                owner_id: other_owner_id,
+            },
+        };
+
+        let response = client
+            .patch(format!("{}/owner/{}/profile", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = client
+            .patch(format!("{}/owner/{}/profile", &address, other_owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", "\"1\"")
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let name: String = sqlx::query_scalar(r#"select name from "owners" where id = $1"#)
+            .bind(other_owner_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        assert_ne!(name, "Jack");
+    }
+
+    #[sqlx::test]
+    async fn test_update_email(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        sqlx::query(r#"update "owners" set email_verified_at = now() where id = $1"#)
+            .bind(owner_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let patch = ApiPayload {
+            payload: UpdateEmail {
                 email: String::from("gray_fox@thepatriots.com"),
-                password: String::from("lalilulelo"),
-                owner_id,
             },
         };
 
-        let request = client
-            .patch(format!("{}/owner/{}/credentials", &address, owner_id))
+        let response = client
+            .patch(format!("{}/owner/{}/email", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
             .json(&patch)
             .send()
             .await
             .unwrap();
 
-        let actual = request.status();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
 
-        let expected = reqwest::StatusCode::NO_CONTENT;
+        let owner: Owners = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
 
-        assert_eq!(actual, expected)
+        assert_eq!(owner.email, "gray_fox@thepatriots.com");
+
+        assert!(owner.email_verified_at.is_none());
+
+        let emails = mailbox.emails();
+
+        assert_eq!(emails.last().unwrap().to, "gray_fox@thepatriots.com");
+    }
+
+    #[sqlx::test]
+    async fn test_update_email_validation(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        utils::create_owner_with_password(&db, "gray_fox@thepatriots.com", "foxdie").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        for (email, status) in [
+            ("gray_fox", reqwest::StatusCode::UNPROCESSABLE_ENTITY),
+            (
+                "gray fox@thepatriots.com",
+                reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                "@thepatriots.com",
+                reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                "gray_fox@thepatriots.",
+                reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            ("gray_fox@thepatriots.com", reqwest::StatusCode::CONFLICT),
+            ("Gray_Fox@ThePatriots.com", reqwest::StatusCode::CONFLICT),
+        ] {
+            let patch = ApiPayload {
+                payload: UpdateEmail {
+                    email: String::from(email),
+                },
+            };
+
+            let response = client
+                .patch(format!("{}/owner/{}/email", &address, owner_id))
+                .bearer_auth(&session)
+                .header("If-Match", utils::etag(&address, &session, owner_id).await)
+                .json(&patch)
+                .send()
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), status, "{}", email);
+        }
+
+        let owner: Owners = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(owner.email, "raiden@dead-cell.test");
+    }
+
+    #[sqlx::test]
+    async fn test_get_another_owner(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other_owner_id = utils::test_setup(&db).await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, other_owner_id))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, other_owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, other_owner_id))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let body: serde_json::Value = response.json().await.unwrap();
+
+        assert!(body.get("password").is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_update_email_of_another_owner(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other_owner_id = utils::test_setup(&db).await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let patch = ApiPayload {
+            payload: UpdateEmail {
+                email: String::from("gray_fox@thepatriots.com"),
+            },
+        };
+
+        let response = reqwest::Client::new()
+            .patch(format!("{}/owner/{}/email", &address, other_owner_id))
+            .bearer_auth(&session)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    async fn test_support_cannot_update_admin(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let admin_id: i32 = sqlx::query_scalar(r#"select id from "owners" where email = $1"#)
+            .bind("colonel@patriots.test")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+
+        let patch = ApiPayload {
+            payload: UpdateEmail {
+                email: String::from("otacon@philanthropy.test.evil"),
+            },
+        };
+
+        let response = client
+            .patch(format!("{}/owner/{}/email", &address, admin_id))
+            .bearer_auth(&support)
+            .header("If-Match", utils::etag(&address, &admin, admin_id).await)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .patch(format!("{}/owner/{}", &address, admin_id))
+            .bearer_auth(&support)
+            .header("If-Match", utils::etag(&address, &admin, admin_id).await)
+            .header("Content-Type", "application/merge-patch+json")
+            .body(r#"{ "email": "otacon@philanthropy.test.evil" }"#)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .patch(format!("{}/owner/{}/email", &address, owner_id))
+            .bearer_auth(&support)
+            .header("If-Match", utils::etag(&address, &support, owner_id).await)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    }
+
+    #[sqlx::test]
+    async fn test_update_password(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other_session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let wrong_current = ApiPayload {
+            payload: UpdatePassword {
+                current_password: String::from("la-li-lu-le-lo"),
+                new_password: String::from("rosemary-and-john"),
+            },
+        };
+
+        let response = client
+            .patch(format!("{}/owner/{}/password", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .json(&wrong_current)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let patch = ApiPayload {
+            payload: UpdatePassword {
+                current_password: String::from("lalilulelo"),
+                new_password: String::from("rosemary-and-john"),
+            },
+        };
+
+        let response = client
+            .patch(format!("{}/owner/{}/password", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        // Other sessions are revoked, the one used for the change survives.
+        let response = client
+            .delete(format!("{}/session", &address))
+            .bearer_auth(&other_session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .delete(format!("{}/session", &address))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "rosemary-and-john").await;
+
+        assert!(!session.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_conditional_profile_updates(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let client = reqwest::Client::new();
+
+        let stale = utils::etag(&address, &session, owner_id).await;
+
+        let rename = |name: &str| ApiPayload {
+            payload: UpdateProfile {
+                name: String::from(name),
+                owner_id,
+            },
+        };
+
+        let response = client
+            .patch(format!("{}/owner/{}/profile", &address, owner_id))
+            .bearer_auth(&session)
+            .json(&rename("Jack"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::PRECONDITION_REQUIRED
+        );
+
+        let response = client
+            .patch(format!("{}/owner/{}/profile", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", &stale)
+            .json(&rename("Jack"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let current = response.headers()["etag"].to_str().unwrap().to_string();
+
+        assert_ne!(current, stale);
+
+        let response = client
+            .patch(format!("{}/owner/{}/profile", &address, owner_id))
+            .bearer_auth(&support)
+            .header("If-Match", &stale)
+            .json(&rename("Snake"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-None-Match", &current)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+        let owner: Owners = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-None-Match", &stale)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(owner.name, "Jack");
+    }
+
+    #[sqlx::test]
+    async fn test_merge_patch_owner(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let patch = |etag: &str, body: serde_json::Value| {
+            client
+                .patch(format!("{}/owner/{}", &address, owner_id))
+                .bearer_auth(&session)
+                .header("If-Match", etag)
+                .header("Content-Type", "application/merge-patch+json")
+                .body(body.to_string())
+        };
+
+        let etag = utils::etag(&address, &session, owner_id).await;
+
+        let response = client
+            .patch(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", &etag)
+            .json(&serde_json::json!({ "name": "Jack" }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        for invalid in [
+            serde_json::json!({ "id": 1337 }),
+            serde_json::json!({ "password": "la-li-lu-le-lo" }),
+            serde_json::json!({ "name": null }),
+            serde_json::json!({ "name": "  " }),
+            serde_json::json!({ "email": 42 }),
+        ] {
+            let response = patch(&etag, invalid).send().await.unwrap();
+
+            assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let response = patch(&etag, serde_json::json!({ "email": "jack@localhost" }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+        utils::create_owner_with_password(&db, "jack@sons-of-liberty.test", "lalilulelo").await;
+
+        let response = patch(
+            &etag,
+            serde_json::json!({ "email": "Jack@Sons-Of-Liberty.test" }),
+        )
+        .send()
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        sqlx::query(r#"update "owners" set deleted_at = now() where email = $1"#)
+            .bind("jack@sons-of-liberty.test")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let response = patch(
+            &etag,
+            serde_json::json!({ "name": "Jack", "email": "jack@sons-of-liberty.test" }),
+        )
+        .send()
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        assert_ne!(response.headers()["etag"], etag.as_str());
+
+        let owner: Owners = response.json().await.unwrap();
+
+        assert_eq!(owner.name, "Jack");
+
+        assert_eq!(owner.email, "jack@sons-of-liberty.test");
+
+        assert!(owner.email_verified_at.is_none());
+
+        assert_eq!(
+            mailbox.emails().last().unwrap().to,
+            "jack@sons-of-liberty.test"
+        );
+
+        let response = patch(&etag, serde_json::json!({ "name": "Raiden" }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
     }
 }
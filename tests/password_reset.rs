@@ -0,0 +1,190 @@
+use proximity_service::{ApiPayload, ConfirmPasswordReset, RequestPasswordReset};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn reset_request(email: &str) -> ApiPayload<RequestPasswordReset> {
+        ApiPayload {
+            payload: RequestPasswordReset {
+                email: String::from(email),
+            },
+        }
+    }
+
+    fn reset_confirmation(token: &str, new_password: &str) -> ApiPayload<ConfirmPasswordReset> {
+        ApiPayload {
+            payload: ConfirmPasswordReset {
+                token: String::from(token),
+                new_password: String::from(new_password),
+            },
+        }
+    }
+
+    async fn verify_email(db: &PgPool, owner_id: i32) {
+        sqlx::query(r#"update "owners" set email_verified_at = now() where id = $1"#)
+            .bind(owner_id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_password_reset(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        verify_email(&db, owner_id).await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/owner/password-reset", &address))
+            .json(&reset_request("raiden@dead-cell.test"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+        mailbox.wait_for_emails(1).await;
+
+        let token = mailbox.last_token();
+
+        let response = client
+            .post(format!("{}/owner/password-reset/confirm", &address))
+            .json(&reset_confirmation(&token, "rosemary-and-john"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let diff: serde_json::Value = sqlx::query_scalar(
+            "select diff from audit_events where target_id = $1 and action = 'owner_password_reset'",
+        )
+        .bind(owner_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(diff["password"]["after"], "[REDACTED]");
+
+        let updates: i64 = sqlx::query_scalar(
+            "select count(*) from outbox where aggregate_id = $1 and event_type = 'owner.updated'",
+        )
+        .bind(owner_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(updates, 1);
+
+        // Existing sessions are revoked by the reset.
+        let response = client
+            .delete(format!("{}/session", &address))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "rosemary-and-john").await;
+
+        assert!(!session.is_empty());
+
+        // Tokens are single use.
+        let response = client
+            .post(format!("{}/owner/password-reset/confirm", &address))
+            .json(&reset_confirmation(&token, "another-password"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn test_password_reset_for_unknown_email(db: PgPool) {
+        let (address, _, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/owner/password-reset", &address))
+            .json(&reset_request("nobody@dead-cell.test"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        assert!(mailbox.emails().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_password_reset_for_unverified_email(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/owner/password-reset", &address))
+            .json(&reset_request("raiden@dead-cell.test"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        assert!(mailbox.emails().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_expired_password_reset_token_is_rejected(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        verify_email(&db, owner_id).await;
+
+        let client = reqwest::Client::new();
+
+        client
+            .post(format!("{}/owner/password-reset", &address))
+            .json(&reset_request("raiden@dead-cell.test"))
+            .send()
+            .await
+            .unwrap();
+
+        mailbox.wait_for_emails(1).await;
+
+        sqlx::query("update password_reset_tokens set expires_at = now() - interval '1 minute'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let response = client
+            .post(format!("{}/owner/password-reset/confirm", &address))
+            .json(&reset_confirmation(
+                &mailbox.last_token(),
+                "rosemary-and-john",
+            ))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+}
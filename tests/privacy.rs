@@ -0,0 +1,264 @@
+use proximity_service::{ApiPayload, CreateWebhook, OwnerExport, Role};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_export_owner(db: PgPool) {
+        let (address, db, _mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        client
+            .post(format!("{}/owner/{}/verify-email", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        let webhook = ApiPayload {
+            payload: CreateWebhook {
+                url: String::from("http://127.0.0.1:9/hooks"),
+                event_types: vec![String::from("owner.updated")],
+                secret: Some(String::from("the-la-li-lu-le-lo-secret")),
+            },
+        };
+
+        client
+            .post(format!("{}/owner/{}/webhooks", &address, owner_id))
+            .bearer_auth(&session)
+            .json(&webhook)
+            .send()
+            .await
+            .unwrap();
+
+        let response = client
+            .get(format!("{}/owner/{}/export", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        assert!(response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+
+        let body = response.text().await.unwrap();
+
+        assert!(!body.contains("argon2"));
+
+        assert!(!body.contains("the-la-li-lu-le-lo-secret"));
+
+        let export: OwnerExport = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(export.owner.email, "raiden@dead-cell.test");
+
+        assert_eq!(export.roles, vec![Role::Owner]);
+
+        assert_eq!(export.sessions.len(), 1);
+
+        assert_eq!(export.email_verifications.len(), 1);
+
+        assert_eq!(export.webhooks.len(), 1);
+
+        assert_eq!(export.webhooks[0].url, "http://127.0.0.1:9/hooks");
+
+        assert_eq!(export.audit_events[0].action, "owner_created");
+
+        let created = export
+            .audit_events
+            .iter()
+            .find(|event| event.action == "webhook_created")
+            .expect("Expected the webhook to be in the audit log");
+
+        assert_eq!(created.diff["webhook_id"]["after"], export.webhooks[0].id);
+
+        assert_eq!(created.diff["secret"]["after"], "[REDACTED]");
+
+        let exports: i64 = sqlx::query_scalar(
+            "select count(*) from audit_events \
+             where action = 'owner_exported' and target_id = $1 and actor_owner_id = $1",
+        )
+        .bind(owner_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(exports, 1);
+    }
+
+    #[sqlx::test]
+    async fn test_export_of_another_owner(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let other_owner_id = utils::test_setup(&db).await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/owner/{}/export", &address, other_owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    async fn test_erase_owner(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let client = reqwest::Client::new();
+
+        let webhook = ApiPayload {
+            payload: CreateWebhook {
+                url: String::from("http://127.0.0.1:9/hooks"),
+                event_types: vec![String::from("owner.updated")],
+                secret: None,
+            },
+        };
+
+        client
+            .post(format!("{}/owner/{}/webhooks", &address, owner_id))
+            .bearer_auth(&session)
+            .json(&webhook)
+            .send()
+            .await
+            .unwrap();
+
+        let response = client
+            .delete(format!("{}/owner/{}?erase=true", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let (name, email): (String, String) =
+            sqlx::query_as(r#"select name, email from "owners" where id = $1"#)
+                .bind(owner_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+
+        assert!(!name.contains("Raiden"));
+
+        assert!(!email.contains("raiden"));
+
+        let sessions: i64 = sqlx::query_scalar("select count(*) from sessions where owner_id = $1")
+            .bind(owner_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        assert_eq!(sessions, 0);
+
+        let webhooks: i64 =
+            sqlx::query_scalar("select count(*) from webhook_subscriptions where owner_id = $1")
+                .bind(owner_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+
+        assert_eq!(webhooks, 0);
+
+        let requested_by: i32 =
+            sqlx::query_scalar("select requested_by from owner_erasures where owner_id = $1")
+                .bind(owner_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+
+        assert_eq!(requested_by, owner_id);
+
+        let response = client
+            .post(format!("{}/admin/owner/{}/restore", &address, owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = client
+            .delete(format!("{}/owner/{}?erase=true", &address, owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_erase_requires_owner_or_admin(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let other_owner_id = utils::test_setup(&db).await;
+
+        let support =
+            utils::login_with_role(&address, &db, "otacon@philanthropy.test", Role::Support).await;
+
+        let admin =
+            utils::login_with_role(&address, &db, "colonel@patriots.test", Role::Admin).await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .delete(format!("{}/owner/{}?erase=true", &address, other_owner_id))
+            .bearer_auth(&support)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .delete(format!("{}/owner/{}?erase=true", &address, other_owner_id))
+            .bearer_auth(&admin)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let admin_id: i32 = sqlx::query_scalar("select id from owners where email = $1")
+            .bind("colonel@patriots.test")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        let requested_by: i32 =
+            sqlx::query_scalar("select requested_by from owner_erasures where owner_id = $1")
+                .bind(other_owner_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+
+        assert_eq!(requested_by, admin_id);
+    }
+}
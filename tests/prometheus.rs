@@ -0,0 +1,63 @@
+use proximity_service::{init_telemetry, MetricsExporter, TelemetryExporter};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_metrics_are_served_in_prometheus_format(db: PgPool) {
+        let mut settings = utils::make_settings();
+
+        settings.telemetry_exporter = TelemetryExporter::None;
+
+        settings.metrics_exporter = MetricsExporter::Prometheus;
+
+        let _telemetry = init_telemetry(&settings).unwrap();
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .get(format!("{}/metrics", &address))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+
+        let body = response.text().await.unwrap();
+
+        assert!(body.contains("# TYPE http_server_requests_total counter"));
+
+        assert!(body.contains("# TYPE http_server_duration histogram"));
+
+        assert!(body.contains(r#"http_route="/owner/:id""#));
+
+        assert!(!body.contains(&format!("/owner/{}\"", owner_id)));
+
+        assert!(body.contains("# TYPE db_client_connections_usage gauge"));
+    }
+}
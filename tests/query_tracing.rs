@@ -0,0 +1,184 @@
+use futures::future::BoxFuture;
+use opentelemetry::{
+    sdk::{
+        export::trace::{ExportResult, SpanData, SpanExporter},
+        trace::TracerProvider,
+    },
+    trace::{SpanId, TracerProvider as _},
+    Key, Value,
+};
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Registry};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct Exported(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Exported {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.0.lock().unwrap().extend(batch);
+
+            Box::pin(std::future::ready(Ok(())))
+        }
+    }
+
+    fn attribute(span: &SpanData, key: &'static str) -> Option<Value> {
+        span.attributes.get(&Key::new(key)).cloned()
+    }
+
+    #[sqlx::test]
+    async fn test_statements_are_traced_and_slow_ones_logged(db: PgPool) {
+        let logs = Captured::default();
+
+        let spans = Exported::default();
+
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(spans.clone())
+            .build();
+
+        let tracer = provider.tracer("tests");
+
+        opentelemetry::global::set_tracer_provider(provider);
+
+        tracing::subscriber::set_global_default(
+            Registry::default()
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(logs.clone())
+                        .with_ansi(false),
+                ),
+        )
+        .unwrap();
+
+        let mut settings = utils::make_settings();
+
+        settings.db_slow_query_threshold_ms = 200;
+
+        let (address, db) = utils::make_server_with_settings(db, settings).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let select = spans
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|span| {
+                span.name == "SELECT"
+                    && attribute(span, "db.statement")
+                        .is_some_and(|sql| sql.as_str().contains(r#"FROM "owners""#))
+            })
+            .cloned()
+            .expect("Expected a span for the owner's SELECT");
+
+        assert_eq!(
+            attribute(&select, "db.system"),
+            Some(Value::from("postgresql"))
+        );
+
+        assert_eq!(attribute(&select, "db.rows_affected"), Some(Value::I64(1)));
+
+        let statement = attribute(&select, "db.statement")
+            .unwrap()
+            .as_str()
+            .into_owned();
+
+        assert!(statement.contains("$1"));
+
+        assert!(!spans.0.lock().unwrap().iter().any(|span| {
+            attribute(span, "db.statement")
+                .is_some_and(|sql| sql.as_str().contains("raiden@dead-cell.test"))
+        }));
+
+        assert_ne!(select.parent_span_id, SpanId::INVALID);
+
+        let mut lock = db.begin().await.unwrap();
+
+        sqlx::query("SELECT id FROM owners WHERE id = $1 FOR UPDATE")
+            .bind(owner_id)
+            .execute(&mut lock)
+            .await
+            .unwrap();
+
+        let etag = utils::etag(&address, &session, owner_id).await;
+
+        let update = tokio::spawn(
+            client
+                .patch(format!("{}/owner/{}", &address, owner_id))
+                .bearer_auth(&session)
+                .header("If-Match", etag)
+                .header("Content-Type", "application/merge-patch+json")
+                .body(serde_json::json!({ "name": "Jack" }).to_string())
+                .send(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+
+        lock.commit().await.unwrap();
+
+        let response = update.await.unwrap().unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+
+        let slow = logs
+            .lines()
+            .filter(|line| line.contains("slow query"))
+            .collect::<Vec<_>>();
+
+        assert!(slow
+            .iter()
+            .any(|line| line.contains("WARN") && line.contains("FOR UPDATE")));
+
+        assert!(!slow.iter().any(|line| line.contains("Jack")));
+    }
+}
@@ -0,0 +1,103 @@
+use proximity_service::{ApiPayload, CreateSession, CreateSessionResponse};
+use std::time::Instant;
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_login(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let credentials = ApiPayload {
+            payload: CreateSession {
+                email: String::from("raiden@dead-cell.test"),
+                password: String::from("lalilulelo"),
+            },
+        };
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/session", &address))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        let session: CreateSessionResponse = response.json().await.unwrap();
+
+        assert_eq!(session.owner_id, owner_id);
+
+        assert!(!session.token.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_login_with_wrong_password(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let mut elapsed = Vec::new();
+
+        for email in ["raiden@dead-cell.test", "nobody@dead-cell.test"] {
+            let credentials = ApiPayload {
+                payload: CreateSession {
+                    email: String::from(email),
+                    password: String::from("la-li-lu-le-lo"),
+                },
+            };
+
+            let started = Instant::now();
+
+            let response = reqwest::Client::new()
+                .post(format!("{}/session", &address))
+                .json(&credentials)
+                .send()
+                .await
+                .unwrap();
+
+            elapsed.push(started.elapsed());
+
+            assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        }
+
+        // An unknown email still pays for a password hash, so it can't be told apart by timing.
+        assert!(elapsed[1] * 2 >= elapsed[0], "{:?}", elapsed);
+    }
+
+    #[sqlx::test]
+    async fn test_logout_revokes_session(db: PgPool) {
+        let (address, db) = utils::make_server(db).await;
+
+        utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let token = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .delete(format!("{}/session", &address))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let response = client
+            .delete(format!("{}/session", &address))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,62 @@
+use proximity_service::{init_telemetry, MetricsExporter, Settings, TelemetryExporter};
+
+mod utils;
+
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_init_rejects_invalid_settings_and_installs_once() {
+        let mut settings = utils::make_settings();
+
+        settings.telemetry_exporter = TelemetryExporter::OtlpGrpc;
+
+        settings.otel_headers = vec![String::from("x-honeycomb-team")];
+
+        assert!(init_telemetry(&settings).is_err());
+
+        settings.otel_headers = vec![String::from("x-honeycomb-team=test")];
+
+        settings.otel_resource_attributes = vec![String::from("deployment.environment")];
+
+        assert!(init_telemetry(&settings).is_err());
+
+        settings.otel_resource_attributes = vec![String::from("deployment.environment=test")];
+
+        settings.telemetry_exporter = TelemetryExporter::OtlpHttp;
+
+        settings.metrics_exporter = MetricsExporter::Telemetry;
+
+        assert!(init_telemetry(&settings).is_err());
+
+        settings.telemetry_exporter = TelemetryExporter::None;
+
+        let guard = init_telemetry(&settings).unwrap();
+
+        tracing::info!("Telemetry is up");
+
+        assert!(init_telemetry(&settings).is_err());
+
+        drop(guard);
+    }
+
+    #[test]
+    fn test_settings_parse_lists_from_environment() {
+        std::env::set_var(
+            "APP_OTEL_HEADERS",
+            "x-honeycomb-team=test,x-honeycomb-dataset=proximity",
+        );
+
+        let settings = Settings::new().unwrap();
+
+        std::env::remove_var("APP_OTEL_HEADERS");
+
+        assert_eq!(
+            settings.otel_headers,
+            vec![
+                String::from("x-honeycomb-team=test"),
+                String::from("x-honeycomb-dataset=proximity"),
+            ]
+        );
+    }
+}
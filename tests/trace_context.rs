@@ -0,0 +1,251 @@
+use axum::{http::HeaderMap, http::StatusCode, routing::post, Router};
+use futures::future::BoxFuture;
+use opentelemetry::{
+    sdk::{
+        export::trace::{ExportResult, SpanData, SpanExporter},
+        trace::TracerProvider,
+    },
+    trace::TracerProvider as _,
+};
+use proximity_service::{ApiPayload, CreateWebhook};
+use std::{
+    net::TcpListener,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod utils;
+
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    /// Spans finished in this test binary.
+    #[derive(Debug, Clone, Default)]
+    struct Exported(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Exported {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.0.lock().unwrap().extend(batch);
+
+            Box::pin(std::future::ready(Ok(())))
+        }
+    }
+
+    impl Exported {
+        /// Polls until a span named `name` matching `filter` was exported.
+        async fn wait_for(&self, name: &str, filter: impl Fn(&SpanData) -> bool) -> SpanData {
+            for _ in 0..50 {
+                let found = self
+                    .0
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|span| span.name == name && filter(span))
+                    .cloned();
+
+                if let Some(span) = found {
+                    return span;
+                }
+
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+
+            panic!("Expected a {} span", name)
+        }
+    }
+
+    fn export_spans() -> Exported {
+        static EXPORTED: OnceLock<Exported> = OnceLock::new();
+
+        EXPORTED
+            .get_or_init(|| {
+                let exported = Exported::default();
+
+                let provider = TracerProvider::builder()
+                    .with_simple_exporter(exported.clone())
+                    .build();
+
+                let tracer = provider.tracer("tests");
+
+                opentelemetry::global::set_tracer_provider(provider);
+
+                tracing::subscriber::set_global_default(
+                    Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer)),
+                )
+                .unwrap();
+
+                exported
+            })
+            .clone()
+    }
+
+    #[sqlx::test]
+    async fn test_valid_request_ids_are_kept(db: PgPool) {
+        let (address, _) = utils::make_server(db).await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/health_check", &address))
+            .header("x-request-id", "gateway-7f3a:42")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["x-request-id"], "gateway-7f3a:42");
+
+        for invalid in ["a".repeat(200), String::from("<script>")] {
+            let response = client
+                .get(format!("{}/health_check", &address))
+                .header("x-request-id", &invalid)
+                .send()
+                .await
+                .unwrap();
+
+            let request_id = response.headers()["x-request-id"].to_str().unwrap();
+
+            assert!(uuid::Uuid::parse_str(request_id).is_ok());
+        }
+
+        let response = client
+            .get(format!("{}/health_check", &address))
+            .send()
+            .await
+            .unwrap();
+
+        let request_id = response.headers()["x-request-id"].to_str().unwrap();
+
+        assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_request_span_continues_incoming_trace(db: PgPool) {
+        let exported = export_spans();
+
+        let (address, _) = utils::make_server(db).await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/health_check", &address))
+            .header(
+                "traceparent",
+                format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID),
+            )
+            .header("tracestate", "vendor=value")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let span = exported
+            .wait_for("http-request", |span| {
+                span.span_context.trace_id().to_string() == TRACE_ID
+            })
+            .await;
+
+        assert_eq!(span.parent_span_id.to_string(), PARENT_SPAN_ID);
+
+        assert_eq!(span.span_context.trace_state().get("vendor"), Some("value"));
+    }
+
+    #[sqlx::test]
+    async fn test_webhook_deliveries_carry_trace_context(db: PgPool) {
+        let exported = export_spans();
+
+        let (address, db) = utils::make_server(db).await;
+
+        let received: Arc<Mutex<Vec<HeaderMap>>> = Arc::default();
+
+        let requests = received.clone();
+
+        let receiver = Router::new().route(
+            "/hooks",
+            post(move |headers: HeaderMap| {
+                requests.lock().unwrap().push(headers);
+
+                async { StatusCode::NO_CONTENT }
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let receiver_url = format!("http://{}/hooks", listener.local_addr().unwrap());
+
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(receiver.into_make_service()),
+        );
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/owner/{}/webhooks", &address, owner_id))
+            .bearer_auth(&session)
+            .json(&ApiPayload {
+                payload: CreateWebhook {
+                    url: receiver_url,
+                    event_types: vec![String::from("owner.updated")],
+                    secret: None,
+                },
+            })
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+        let response = client
+            .patch(format!("{}/owner/{}", &address, owner_id))
+            .bearer_auth(&session)
+            .header("If-Match", utils::etag(&address, &session, owner_id).await)
+            .header("Content-Type", "application/merge-patch+json")
+            .body(serde_json::json!({ "name": "Jack" }).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let mut traceparent = None;
+
+        for _ in 0..50 {
+            traceparent = received
+                .lock()
+                .unwrap()
+                .first()
+                .map(|headers| headers["traceparent"].to_str().unwrap().to_string());
+
+            if traceparent.is_some() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let traceparent = traceparent.expect("Expected a webhook request");
+
+        let parts: Vec<&str> = traceparent.split('-').collect();
+
+        assert_eq!(parts.len(), 4);
+
+        exported
+            .wait_for("POST a webhook delivery", |span| {
+                span.span_context.trace_id().to_string() == parts[1]
+                    && span.span_context.span_id().to_string() == parts[2]
+            })
+            .await;
+    }
+}
@@ -1,11 +1,45 @@
 use dotenvy::dotenv;
-use std::net::TcpListener;
+use proximity_service::{
+    create_owner, grant_role, hash_password, Actor, ApiPayload, CreateOwner, CreateSession,
+    CreateSessionResponse, Email, MailerKind, Role, Settings,
+};
+use std::{net::TcpListener, path::PathBuf, time::Duration};
+use uuid::Uuid;
 
 use sqlx::{PgPool, Pool, Postgres};
 
-pub async fn make_server(db: PgPool) -> (String, Pool<Postgres>) {
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub fn make_settings() -> Settings {
     dotenv().ok();
 
+    let mut settings = Settings::new().unwrap();
+
+    settings.mailer_kind = MailerKind::Memory;
+
+    settings.event_sink_file_path = std::env::temp_dir()
+        .join("proximity-service-events")
+        .join(Uuid::new_v4().to_string())
+        .join("events.jsonl")
+        .to_string_lossy()
+        .into_owned();
+
+    settings.outbox_dispatch_interval_ms = 50;
+
+    settings.webhook_dispatch_interval_ms = 50;
+
+    // Receivers in tests listen on 127.0.0.1.
+    settings.webhook_allow_private_targets = true;
+
+    settings
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn make_server(db: PgPool) -> (String, Pool<Postgres>) {
+    make_server_with_settings(db, make_settings()).await
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn make_server_with_settings(db: PgPool, settings: Settings) -> (String, Pool<Postgres>) {
     let addr = TcpListener::bind("127.0.0.1:0")
         .expect("Failed to bind to random port")
         .local_addr()
@@ -13,8 +47,6 @@ pub async fn make_server(db: PgPool) -> (String, Pool<Postgres>) {
 
     let port = addr.port();
 
-    let settings = proximity_service::Settings::new().unwrap();
-
     let server = proximity_service::serve(&addr, db.clone(), settings);
 
     tokio::spawn(server);
@@ -22,6 +54,75 @@ pub async fn make_server(db: PgPool) -> (String, Pool<Postgres>) {
     (format!("http://127.0.0.1:{}", port), db)
 }
 
+/// Emails written by the server's `FileMailer`.
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub struct Mailbox {
+    dir: PathBuf,
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+impl Mailbox {
+    pub fn emails(&self) -> Vec<Email> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut paths: Vec<PathBuf> = entries.map(|entry| entry.unwrap().path()).collect();
+
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap())
+            .collect()
+    }
+
+    /// Polls until at least `count` emails were written, for mail sent in the background.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<Email> {
+        for _ in 0..50 {
+            let emails = self.emails();
+
+            if emails.len() >= count {
+                return emails;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("Expected {} email(s) to be sent", count)
+    }
+
+    /// Extracts the `token` query parameter from the most recent email.
+    pub fn last_token(&self) -> String {
+        let email = self.emails().pop().expect("Expected an email to be sent");
+
+        email
+            .body
+            .split("token=")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .expect("Expected the email to contain a token")
+            .to_string()
+    }
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn make_server_with_mailbox(db: PgPool) -> (String, Pool<Postgres>, Mailbox) {
+    let mut settings = make_settings();
+
+    let dir = std::env::temp_dir()
+        .join("proximity-service-mail")
+        .join(Uuid::new_v4().to_string());
+
+    settings.mailer_kind = MailerKind::File;
+
+    settings.mailer_file_dir = dir.to_string_lossy().into_owned();
+
+    let (address, db) = make_server_with_settings(db, settings).await;
+
+    (address, db, Mailbox { dir })
+}
+
 #[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
 pub async fn test_setup(db: &Pool<Postgres>) -> i32 {
     /*! Seeds database with a single "Owner" record */
@@ -45,3 +146,68 @@ pub async fn test_teardown(id: i32, db: &Pool<Postgres>) -> Result<(), ()> {
 
     Ok(())
 }
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn create_owner_with_password(db: &Pool<Postgres>, email: &str, password: &str) -> i32 {
+    /*! Seeds database with an "Owner" record whose password is hashed, so it can log in */
+
+    let owner = CreateOwner {
+        name: String::from("Raiden"),
+        email: String::from(email),
+        password: hash_password(String::from(password)).await.unwrap(),
+    };
+
+    create_owner(owner, &Actor::system(), db).await.unwrap().id
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn login(address: &str, email: &str, password: &str) -> String {
+    let credentials = ApiPayload {
+        payload: CreateSession {
+            email: String::from(email),
+            password: String::from(password),
+        },
+    };
+
+    let session: CreateSessionResponse = reqwest::Client::new()
+        .post(format!("{}/session", address))
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    session.token
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn login_with_role(
+    address: &str,
+    db: &Pool<Postgres>,
+    email: &str,
+    role: Role,
+) -> String {
+    /*! Seeds an "Owner" holding `role` and returns a session token for it */
+
+    let owner_id = create_owner_with_password(db, email, "metal-gear-rex").await;
+
+    grant_role(owner_id, role, db).await.unwrap();
+
+    login(address, email, "metal-gear-rex").await
+}
+
+#[allow(dead_code)] // bug: https://github.com/rust-lang/rust/issues/46379
+pub async fn etag(address: &str, session: &str, owner_id: i32) -> String {
+    /*! Reads the current `ETag` of an "Owner", as required by `If-Match` on updates */
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/owner/{}", address, owner_id))
+        .bearer_auth(session)
+        .send()
+        .await
+        .unwrap();
+
+    response.headers()["etag"].to_str().unwrap().to_string()
+}
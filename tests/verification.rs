@@ -0,0 +1,153 @@
+mod utils;
+
+mod tests {
+    use proximity_service::{ApiPayload, CreateOwner, CreateOwnerResponse, Owners};
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_post_owner_sends_verification_email(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let new_owner = ApiPayload {
+            payload: CreateOwner {
+                name: String::from("Hal Emmerich"),
+                email: String::from("otacon@philanthropy.test"),
+                password: String::from("lalilulelo"),
+            },
+        };
+
+        let client = reqwest::Client::new();
+
+        let owner: CreateOwnerResponse = client
+            .post(format!("{}/owner", &address))
+            .json(&new_owner)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        let emails = mailbox.emails();
+
+        assert_eq!(emails.len(), 1);
+
+        assert_eq!(emails[0].to, "otacon@philanthropy.test");
+
+        let response = client
+            .get(format!(
+                "{}/verify?token={}",
+                &address,
+                mailbox.last_token()
+            ))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let session = utils::login(&address, "otacon@philanthropy.test", "lalilulelo").await;
+
+        let owner: Owners = client
+            .get(format!("{}/owner/{}", &address, owner.id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert!(owner.email_verified_at.is_some());
+
+        let verified: i64 = sqlx::query_scalar(
+            "select count(*) from audit_events where target_id = $1 and action = 'owner_email_verified'",
+        )
+        .bind(owner.id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(verified, 1);
+    }
+
+    #[sqlx::test]
+    async fn test_verification_token_is_single_use(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/owner/{}/verify-email", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+        let verify_url = format!("{}/verify?token={}", &address, mailbox.last_token());
+
+        let first = client.get(&verify_url).send().await.unwrap();
+
+        assert_eq!(first.status(), reqwest::StatusCode::OK);
+
+        let second = client.get(&verify_url).send().await.unwrap();
+
+        assert_eq!(second.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post(format!("{}/owner/{}/verify-email", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    }
+
+    #[sqlx::test]
+    async fn test_expired_verification_token_is_rejected(db: PgPool) {
+        let (address, db, mailbox) = utils::make_server_with_mailbox(db).await;
+
+        let owner_id =
+            utils::create_owner_with_password(&db, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let session = utils::login(&address, "raiden@dead-cell.test", "lalilulelo").await;
+
+        let client = reqwest::Client::new();
+
+        client
+            .post(format!("{}/owner/{}/verify-email", &address, owner_id))
+            .bearer_auth(&session)
+            .send()
+            .await
+            .unwrap();
+
+        sqlx::query(
+            "update email_verification_tokens set expires_at = now() - interval '1 minute'",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let response = client
+            .get(format!(
+                "{}/verify?token={}",
+                &address,
+                mailbox.last_token()
+            ))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+}